@@ -1,15 +1,268 @@
 //
 
-#[derive(Debug, Fail)]
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use crate::core::runtime::FrameInfo;
+
+use hulunbuir::Error as CollectorError;
+
+#[derive(Debug)]
 pub enum Error {
-    #[fail(display = "type mismatch")]
-    TypeMismatch,
-    #[fail(display = "object is used by others")]
+    TypeMismatch {
+        expected: &'static str,
+        actual: &'static str,
+    },
     BusyObject,
-    #[fail(display = "not callable")]
     NotCallable,
-    #[fail(display = "pop empty stack")]
     ExhaustedFrame,
-    #[fail(display = "no parent frame")]
     NoParentFrame,
+    IndexOutOfRange,
+    InvalidUtf8,
+    NoSuchMethod {
+        class: String,
+        name: String,
+    },
+    FillWithoutTake,
+    OutOfMemory {
+        live: usize,
+        capacity: usize,
+    },
+    // the reserve for frames is exhausted as well, the runtime should not be used anymore
+    InternalOutOfMemory,
+    UnwrappedErr(String),
+    Cancelled,
+    ConcurrentModification,
+    InvalidFormat,
+    FormatArguments {
+        placeholders: usize,
+        arguments: usize,
+    },
+    Io(io::Error),
+    StackOverflow,
+    NotTransferable,
+    ParseError {
+        input: String,
+        target_type: &'static str,
+    },
+    FloatToInt(f64),
+    DivisionByZero,
+    IntegerOverflow,
+    SuspendWhileTaken,
+    NotInGenerator,
+    GeneratorFinished,
+    TailCallInGenerator,
+    DeadlockDetected {
+        cycle: Vec<String>,
+    },
+    UnknownSuite(String),
+    SuiteCycle(Vec<String>),
+    NoSuchGlobal(String),
+    NotHashable(&'static str),
+    // also for values of an ordered type which are not comparable, like NaN
+    NotOrdered {
+        left: &'static str,
+        right: &'static str,
+    },
+    Abandoned,
+    Pinned,
+    // an error raised while cleaning up after another one
+    Cleanup {
+        error: Box<Error>,
+        cleanup: Box<Error>,
+    },
+    Collector(CollectorError),
+    // the frames innermost first, as when the error was raised in a method
+    WithBacktrace {
+        source: Box<Error>,
+        frames: Vec<FrameInfo>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::TypeMismatch { expected, actual } => {
+                write!(f, "type mismatch: expected {}, found {}", expected, actual)
+            }
+            Error::BusyObject => write!(f, "object is used by others"),
+            Error::NotCallable => write!(f, "not callable"),
+            Error::ExhaustedFrame => write!(f, "pop empty stack"),
+            Error::NoParentFrame => write!(f, "no parent frame"),
+            Error::IndexOutOfRange => write!(f, "index out of range"),
+            Error::InvalidUtf8 => write!(f, "invalid utf-8"),
+            Error::NoSuchMethod { class, name } => {
+                write!(f, "no method {} in class {}", name, class)
+            }
+            Error::FillWithoutTake => write!(f, "fill an object which was not taken"),
+            Error::OutOfMemory { live, capacity } => {
+                write!(f, "out of memory: {} of {} slots alive", live, capacity)
+            }
+            Error::InternalOutOfMemory => write!(f, "out of memory for frames"),
+            Error::UnwrappedErr(payload) => write!(f, "unwrap an err result: {}", payload),
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::ConcurrentModification => write!(f, "list or dict changed during iteration"),
+            Error::Cleanup { error, cleanup } => {
+                write!(f, "{} (then cleanup failed: {})", error, cleanup)
+            }
+            Error::InvalidFormat => write!(f, "invalid format template"),
+            Error::FormatArguments {
+                placeholders,
+                arguments,
+            } => write!(
+                f,
+                "{} placeholders but {} arguments in format",
+                placeholders, arguments
+            ),
+            Error::Io(err) => write!(f, "io: {}", err),
+            Error::StackOverflow => write!(f, "too many nested calls"),
+            Error::NotTransferable => write!(f, "object cannot be moved between memories"),
+            Error::ParseError { input, target_type } => {
+                write!(f, "cannot parse {:?} as {}", input, target_type)
+            }
+            Error::FloatToInt(value) => write!(f, "{} does not fit in an int", value),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::IntegerOverflow => write!(f, "integer overflow"),
+            Error::SuspendWhileTaken => write!(f, "suspend with objects taken out"),
+            Error::NotInGenerator => write!(f, "yield outside of a generator"),
+            Error::GeneratorFinished => write!(f, "resume a finished generator"),
+            Error::TailCallInGenerator => write!(f, "tail call from a generator's frame"),
+            Error::DeadlockDetected { cycle } => write!(f, "deadlock: {}", cycle.join(", ")),
+            Error::UnknownSuite(name) => write!(f, "unknown suite {}", name),
+            Error::SuiteCycle(cycle) => {
+                write!(f, "suites depend on each other: {}", cycle.join(" -> "))
+            }
+            Error::NoSuchGlobal(name) => write!(f, "no global {}", name),
+            Error::NotHashable(type_name) => write!(f, "{} is not hashable", type_name),
+            Error::NotOrdered { left, right } => {
+                write!(f, "cannot compare {} with {}", left, right)
+            }
+            Error::Abandoned => write!(f, "object was taken by a runtime dropped since"),
+            Error::Pinned => write!(f, "object is pinned"),
+            Error::Collector(err) => write!(f, "collector: {}", err),
+            Error::WithBacktrace { source, frames } => {
+                write!(f, "{}\n  at ", source)?;
+                for (position, frame) in frames.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, " <- ")?;
+                    }
+                    write!(f, "{}", frame)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// `Error` is `Send + Sync + 'static`, so downstream code still on `failure` gets
+// `failure::Error: From<Error>` through failure's blanket `Fail` impl
+impl StdError for Error {
+    // a cleanup error is reported after the error it was cleaning up for, which is the source
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Collector(err) => Some(collector_source(err)),
+            Error::Cleanup { error, .. } => Some(&**error),
+            Error::WithBacktrace { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+// the source of `Error::Collector`, as the collector's errors only implement `failure::Fail`
+#[derive(Debug)]
+pub struct CollectorSource(CollectorError);
+
+impl CollectorSource {
+    pub fn error(&self) -> &CollectorError {
+        &self.0
+    }
+}
+
+impl fmt::Display for CollectorSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl StdError for CollectorSource {}
+
+// the collector's errors carry nothing, so there is one source for each of them
+static OUT_OF_SLOTS: CollectorSource = CollectorSource(CollectorError::OutOfSlots);
+static INVALID_ADDRESS: CollectorSource = CollectorSource(CollectorError::InvalidAddress);
+static DUPLICATED_FILLING: CollectorSource = CollectorSource(CollectorError::DuplicatedFilling);
+
+fn collector_source(err: &CollectorError) -> &'static CollectorSource {
+    match err {
+        CollectorError::OutOfSlots => &OUT_OF_SLOTS,
+        CollectorError::InvalidAddress => &INVALID_ADDRESS,
+        CollectorError::DuplicatedFilling => &DUPLICATED_FILLING,
+    }
+}
+
+impl Error {
+    // the error itself, without any backtrace which came along
+    pub fn without_backtrace(self) -> Self {
+        match self {
+            Error::WithBacktrace { source, .. } => *source,
+            err => err,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<CollectorError> for Error {
+    fn from(err: CollectorError) -> Self {
+        Error::Collector(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes_as_std_error() {
+        let err: Box<dyn StdError + Send + Sync> = Box::new(Error::FillWithoutTake);
+        assert_eq!(err.to_string(), "fill an object which was not taken");
+        let err: Box<dyn StdError + Send + Sync> = Error::ExhaustedFrame.into();
+        assert_eq!(err.to_string(), "pop empty stack");
+    }
+
+    #[test]
+    fn chains_sources() {
+        assert!(Error::FillWithoutTake.source().is_none());
+        let err = Error::from(io::Error::other("disk"));
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "disk");
+        assert!(source.downcast_ref::<io::Error>().is_some());
+
+        let err = Error::from(CollectorError::InvalidAddress);
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "invalid address");
+        let source = source.downcast_ref::<CollectorSource>().unwrap();
+        assert!(matches!(source.error(), CollectorError::InvalidAddress));
+
+        let err = Error::Cleanup {
+            error: Box::new(Error::Io(io::Error::other("disk"))),
+            cleanup: Box::new(Error::BusyObject),
+        };
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "io: disk");
+        assert_eq!(source.source().unwrap().to_string(), "disk");
+
+        let err = Error::WithBacktrace {
+            source: Box::new(Error::Cancelled),
+            frames: Vec::new(),
+        };
+        assert_eq!(err.source().unwrap().to_string(), "cancelled");
+    }
 }
@@ -3,4 +3,9 @@
 pub mod error;
 // pub mod memory;
 pub mod object;
+pub mod profile;
 pub mod runtime;
+pub mod sched;
+pub mod suite;
+pub mod view;
+pub mod walk;
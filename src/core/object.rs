@@ -1,6 +1,9 @@
 //
 
 use std::any::Any;
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
 
 use crate::core::error::Error;
 
@@ -8,40 +11,321 @@ use hulunbuir::{Keep, Address};
 
 pub struct Object {
     content: Box<dyn Any>,
-    keep: fn(&Object) -> Vec<Address>,
+    vtable: &'static ObjectVTable,
+}
+
+// per-type capabilities, shared by every object of the type so that each object
+// pays for one pointer no matter how many capabilities exist
+pub struct ObjectVTable {
+    pub type_name: fn() -> &'static str,
+    pub keep: fn(&Object) -> Vec<Address>,
+    pub debug: Option<fn(&Object, &mut fmt::Formatter) -> fmt::Result>,
+    pub display: Option<fn(&Object, &mut fmt::Formatter) -> fmt::Result>,
+    pub clone: Option<fn(&Object) -> Object>,
+    pub finalize: Option<fn(&mut Object)>,
+    // equal objects must hash the same, both only for objects of the same type
+    pub hash: Option<fn(&Object) -> u64>,
+    pub cmp: Option<fn(&Object, &Object) -> Option<Ordering>>,
+}
+
+impl ObjectVTable {
+    pub const fn keep_only<T: Any + Keep>() -> Self {
+        Self {
+            type_name: std::any::type_name::<T>,
+            keep: keep_helper::<T>,
+            debug: None,
+            display: None,
+            clone: None,
+            finalize: None,
+            hash: None,
+            cmp: None,
+        }
+    }
+}
+
+pub trait HasVTable: Any + Keep + Sized {
+    const VTABLE: ObjectVTable = ObjectVTable::keep_only::<Self>();
+}
+
+pub trait Finalize {
+    fn finalize(&mut self);
+}
+
+pub mod capability {
+    use std::any::Any;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::fmt;
+    use std::hash::{Hash, Hasher};
+
+    use super::{Finalize, Object};
+
+    pub fn debug<T: Any + fmt::Debug>(object: &Object, f: &mut fmt::Formatter) -> fmt::Result {
+        object.downcast_ref::<T>().unwrap().fmt(f)
+    }
+
+    pub fn display<T: Any + fmt::Display>(object: &Object, f: &mut fmt::Formatter) -> fmt::Result {
+        object.downcast_ref::<T>().unwrap().fmt(f)
+    }
+
+    pub fn clone<T: Any + Clone>(object: &Object) -> Object {
+        Object {
+            content: Box::new(object.downcast_ref::<T>().unwrap().clone()),
+            vtable: object.vtable,
+        }
+    }
+
+    pub fn finalize<T: Any + Finalize>(object: &mut Object) {
+        object.downcast_mut::<T>().unwrap().finalize()
+    }
+
+    // `DefaultHasher::new` uses fixed keys, so hashes are stable within one build
+    pub fn hash<T: Any + Hash>(object: &Object) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        object.downcast_ref::<T>().unwrap().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // objects of different types are not ordered
+    pub fn cmp<T: Any + PartialOrd>(object: &Object, other: &Object) -> Option<Ordering> {
+        let other = other.downcast_ref::<T>().ok()?;
+        object.downcast_ref::<T>().unwrap().partial_cmp(other)
+    }
+}
+
+// implements `HasVTable` for a type with the listed capabilities, e.g.
+// `capability!(Int: debug, clone);`
+#[macro_export]
+macro_rules! capability {
+    ($type:ty: $($capability:ident),*) => {
+        impl $crate::core::object::HasVTable for $type {
+            const VTABLE: $crate::core::object::ObjectVTable =
+                $crate::core::object::ObjectVTable {
+                    $($capability: Some($crate::core::object::capability::$capability::<$type>),)*
+                    ..$crate::core::object::ObjectVTable::keep_only::<$type>()
+                };
+        }
+    };
 }
 
 impl Keep for Object {
     fn with_keep<F: FnOnce(&[Address])>(&self, f: F) {
-        f(&(self.keep)(self))
+        f(&(self.vtable.keep)(self))
     }
 }
 
 fn keep_helper<T: Any + Keep>(object: &Object) -> Vec<Address> {
     let mut keep_list = Vec::new();
-    object.downcast_ref::<T>().unwrap().with_keep(|list| keep_list = list.to_vec());
+    object.downcast_ref::<T>().unwrap().with_keep(|list| keep_list.extend_from_slice(list));
     keep_list
 }
 
+struct KeepOnly<T>(PhantomData<T>);
+
+// left behind in an object whose content was moved out
+struct Moved;
+
+impl Keep for Moved {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+impl<T: Any + Keep> KeepOnly<T> {
+    const VTABLE: ObjectVTable = ObjectVTable::keep_only::<T>();
+}
+
 impl Object {
     pub fn new<T: Any + Keep>(content: T) -> Self {
         Object {
             content: Box::new(content),
-            keep: keep_helper::<T>,
+            vtable: &KeepOnly::<T>::VTABLE,
+        }
+    }
+
+    pub fn with_vtable<T: HasVTable>(content: T) -> Self {
+        Object {
+            content: Box::new(content),
+            vtable: &T::VTABLE,
         }
     }
 }
 
 impl Object {
     pub fn downcast_ref<T: Any>(&self) -> Result<&T, Error> {
-        (&self.content as &dyn Any).downcast_ref().ok_or(Error::TypeMismatch)
+        let mismatch = self.mismatch::<T>();
+        self.content.downcast_ref().ok_or(mismatch)
     }
 
     pub fn downcast_mut<T: Any>(&mut self) -> Result<&mut T, Error> {
-        (&mut self.content as &mut dyn Any).downcast_mut().ok_or(Error::TypeMismatch)
+        let mismatch = self.mismatch::<T>();
+        self.content.downcast_mut().ok_or(mismatch)
     }
 
+    // the object is dropped on mismatch, use `try_downcast` to get it back
     pub fn downcast<T: Any>(self) -> Result<T, Error> {
-        Ok(*self.content.downcast().map_err(|_| Error::TypeMismatch)?)
+        self.try_downcast().map_err(|(_, err)| err)
+    }
+
+    // the content is handed on, so it is not finalized
+    pub fn try_downcast<T: Any>(mut self) -> Result<T, (Object, Error)> {
+        if !self.content.is::<T>() {
+            let mismatch = self.mismatch::<T>();
+            return Err((self, mismatch));
+        }
+        let content = std::mem::replace(&mut self.content, Box::new(Moved));
+        self.vtable = &KeepOnly::<Moved>::VTABLE;
+        Ok(*content.downcast().unwrap())
+    }
+
+    fn mismatch<T: Any>(&self) -> Error {
+        Error::TypeMismatch {
+            expected: std::any::type_name::<T>(),
+            actual: self.type_name(),
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Object {
+    pub fn as_any(&self) -> &dyn Any {
+        &*self.content
+    }
+
+    pub fn vtable(&self) -> &'static ObjectVTable {
+        self.vtable
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        (self.vtable.type_name)()
+    }
+
+    pub fn try_clone(&self) -> Option<Object> {
+        self.vtable.clone.map(|clone| clone(self))
+    }
+
+    pub fn hash_value(&self) -> Result<u64, Error> {
+        let hash = self.vtable.hash.ok_or(Error::NotHashable(self.type_name()))?;
+        Ok(hash(self))
+    }
+
+    pub fn compare(&self, other: &Object) -> Result<Ordering, Error> {
+        self.vtable.cmp.and_then(|cmp| cmp(self, other)).ok_or(Error::NotOrdered {
+            left: self.type_name(),
+            right: other.type_name(),
+        })
+    }
+
+    pub fn display_string(&self) -> Option<String> {
+        struct Display<'a>(&'a Object, fn(&Object, &mut fmt::Formatter) -> fmt::Result);
+        impl fmt::Display for Display<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                (self.1)(self.0, f)
+            }
+        }
+        self.vtable.display.map(|display| Display(self, display).to_string())
+    }
+
+    pub fn finalize(&mut self) -> bool {
+        match self.vtable.finalize {
+            Some(finalize) => {
+                finalize(self);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// whoever drops the object last finalizes it, be it the collector reclaiming it or the
+// holder of an object taken out for good
+impl Drop for Object {
+    fn drop(&mut self) {
+        self.finalize();
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.vtable.debug {
+            Some(debug) => debug(self, f),
+            None => write!(f, "<object>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::mem;
+    use std::rc::Rc;
+
+    use hulunbuir::Address;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Int(i64);
+
+    impl Keep for Int {
+        fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+    }
+
+    crate::capability!(Int: debug, clone);
+
+    struct Resource(Rc<Cell<bool>>);
+
+    impl Keep for Resource {
+        fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+    }
+
+    impl Finalize for Resource {
+        fn finalize(&mut self) {
+            self.0.set(true)
+        }
+    }
+
+    crate::capability!(Resource: finalize);
+
+    struct Plain;
+
+    impl Keep for Plain {
+        fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+    }
+
+    impl HasVTable for Plain {}
+
+    #[test]
+    fn dispatches_through_vtable() {
+        let object = Object::with_vtable(Int(42));
+        assert_eq!(format!("{:?}", object), "Int(42)");
+        let cloned = object.try_clone().unwrap();
+        assert_eq!(cloned.downcast_ref::<Int>().unwrap(), &Int(42));
+        assert_eq!(format!("{:?}", cloned), "Int(42)");
+        assert_eq!(format!("{:?}", Object::new(Int(1))), "<object>");
+        assert!(Object::new(Int(1)).try_clone().is_none());
+        assert!(Object::with_vtable(Plain).try_clone().is_none());
+
+        let finalized = Rc::new(Cell::new(false));
+        let mut resource = Object::with_vtable(Resource(finalized.to_owned()));
+        assert!(resource.finalize());
+        assert!(finalized.get());
+        assert!(!Object::new(Plain).finalize());
+        finalized.set(false);
+        drop(resource);
+        assert!(finalized.get());
+        finalized.set(false);
+        let resource = Object::with_vtable(Resource(finalized.to_owned()));
+        let content = resource.downcast::<Resource>().ok().unwrap();
+        assert!(!finalized.get());
+        drop(content);
+        assert!(!finalized.get());
+        assert_eq!(mem::size_of::<Object>(), 3 * mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn failed_downcast_gives_object_back() {
+        let object = Object::with_vtable(Int(3));
+        let (object, err) = object.try_downcast::<Plain>().err().unwrap();
+        assert!(err.to_string().contains("Int"));
+        assert_eq!(format!("{:?}", object), "Int(3)");
+        assert_eq!(object.downcast::<Int>().unwrap(), Int(3));
+    }
+}
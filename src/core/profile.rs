@@ -0,0 +1,77 @@
+//
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use hulunbuir::Address;
+
+#[derive(Debug, Clone)]
+pub struct MethodProfile {
+    // methods are told apart by the context they are called with
+    pub context: Address,
+    pub calls: usize,
+    // recursive calls are counted once per level
+    pub inclusive: Duration,
+    // without the time spent in callees
+    pub exclusive: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct Profiler {
+    // one entry per frame above the root one, innermost last
+    running: Vec<Running>,
+    profiles: HashMap<Address, MethodProfile>,
+}
+
+struct Running {
+    context: Address,
+    start: Instant,
+    callees: Duration,
+}
+
+impl Profiler {
+    pub(crate) fn enter(&mut self, context: Address) {
+        self.running.push(Running {
+            context,
+            start: Instant::now(),
+            callees: Duration::default(),
+        });
+    }
+
+    pub(crate) fn exit(&mut self) {
+        let running = match self.running.pop() {
+            Some(running) => running,
+            None => return,
+        };
+        let inclusive = running.start.elapsed();
+        if let Some(caller) = self.running.last_mut() {
+            caller.callees += inclusive;
+        }
+        let exclusive = inclusive.saturating_sub(running.callees);
+        let profile = self
+            .profiles
+            .entry(running.context.to_owned())
+            .or_insert_with(|| MethodProfile {
+                context: running.context,
+                calls: 0,
+                inclusive: Duration::default(),
+                exclusive: Duration::default(),
+            });
+        profile.calls += 1;
+        profile.inclusive += inclusive;
+        profile.exclusive += exclusive;
+    }
+
+    // most exclusive time first
+    pub(crate) fn report(&self) -> Vec<MethodProfile> {
+        let mut report: Vec<_> = self.profiles.values().cloned().collect();
+        report.sort_by_key(|profile| Reverse(profile.exclusive));
+        report
+    }
+
+    // calls still running are recorded in full once they return
+    pub(crate) fn reset(&mut self) {
+        self.profiles.clear();
+    }
+}
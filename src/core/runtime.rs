@@ -1,37 +1,309 @@
 //
 
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::core::error::Error as ShattuckError;
-use crate::core::object::Object;
+use crate::core::error::{Error, Result};
+use crate::core::object::{HasVTable, Object};
+use crate::core::profile::{MethodProfile, Profiler};
+use crate::core::sched::{NoopScheduler, Scheduler};
+use crate::core::suite::{self, BuiltinSuite, Suites};
+use crate::core::view::{FrameView, RuntimeView, ViewHandle, BUSY};
+use crate::core::walk;
+use crate::objects::atomic::AtomicIntObject;
+use crate::objects::builtin::builtin_suites;
+use crate::objects::cancel::CancellationToken;
+use crate::objects::class::ClassObject;
+use crate::objects::dict::ObjectDict;
+use crate::objects::float::{FloatObject, RoundMode};
+use crate::objects::fmt;
+use crate::objects::generator::GeneratorObject;
+use crate::objects::int::IntObject;
+use crate::objects::list::ListObject;
+use crate::objects::native::NativeMethod;
+use crate::objects::nil::NilObject;
+use crate::objects::numeric::{Number, NumericMode};
+use crate::objects::result::ResultObject;
+use crate::objects::string::StringObject;
+use crate::objects::time::{DeadlineObject, StopwatchObject};
 
-use failure::Error;
 use hulunbuir::{
     slot::{Slot, Take},
-    Address, Collector as RawCollector, Keep,
+    Address, Collector as RawCollector, Error as CollectorError, Keep,
 };
 use parking_lot::Mutex;
 
 pub type Collector = Arc<Mutex<RawCollector<Slot<Object>>>>;
 
+type Deferred = Box<dyn FnOnce(&mut Runtime) -> Result<()>>;
+
+type Pins = Arc<Mutex<HashMap<Address, usize>>>;
+
+static NEXT_RUNTIME_ID: AtomicUsize = AtomicUsize::new(0);
+
+// objects taken by the runtimes of this thread, through `take` or `wait`, a handle or a
+// cached frame, keyed by their collector; a collector never leaves its thread, so nobody
+// can fill these while this thread is parked waiting for one of them
+thread_local! {
+    static HOLDERS: RefCell<HashMap<(usize, Address), Holder>> = RefCell::new(HashMap::new());
+}
+
+struct Holder {
+    runtime: usize,
+    type_name: &'static str,
+}
+
+fn holder_key(memory: &Collector, address: &Address) -> (usize, Address) {
+    (Arc::as_ptr(memory) as usize, address.to_owned())
+}
+
 pub struct Runtime {
+    id: usize,
     memory: Collector,
     frame_stack: Vec<Address>,
+    // current frame object while it is checked out of the collector, and how many
+    // (nested) operations are holding it
+    frame_cache: Option<Frame>,
+    frame_hold: usize,
+    scheduler: Arc<dyn Scheduler + Send + Sync>,
+    cancellation: CancellationToken,
+    // idle frame slots reused by calls instead of allocating new ones
+    frame_pool: Vec<Address>,
+    frame_pool_size: usize,
+    // the frame of a tail call while the replaced frame runs its deferred steps
+    pending_frame: Option<Address>,
+    // objects taken through `take` or `wait` and not filled back yet, also those taken
+    // by frames which returned meanwhile and so can't fill them any more
+    taken: HashSet<Address>,
+    // collector root, refreshed before every allocation since any of them may collect
+    root: Address,
+    pins: Pins,
+    allocation_limit: Option<usize>,
+    max_depth: Option<usize>,
+    // the value yielded by each generator being resumed, innermost last
+    generator_yields: Vec<Option<Address>>,
+    globals: HashMap<String, Address>,
+    suites: Suites,
+    installed: Vec<String>,
+    collector_capacity: Option<usize>,
+    nil: Address,
+    profiler: Option<Profiler>,
+    view_handle: Option<ViewHandle>,
+    stdout: Box<dyn Write + Send>,
+    numeric_mode: NumericMode,
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        debug_assert!(
+            std::thread::panicking() || self.pins.lock().is_empty(),
+            "runtime dropped with live pins"
+        );
+        self.abandon_takes();
+        // the root object itself stays, since the roots of runtimes booted later chain up
+        // through it
+        let _ = refresh_root(&mut self.memory.lock(), &self.root, Vec::new());
+    }
+}
+
+// chains up the root which was set before booting, so that several runtimes (and the
+// embedder) can share one collector
+struct RuntimeRoot {
+    // frames and pinned objects
+    roots: Vec<Address>,
+    previous: Option<Address>,
+}
+
+impl Keep for RuntimeRoot {
+    fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+        f(&self.roots);
+        if let Some(previous) = &self.previous {
+            f(&[previous.to_owned()]);
+        }
+    }
+}
+
+fn refresh_root(
+    memory: &mut RawCollector<Slot<Object>>,
+    root: &Address,
+    roots: Vec<Address>,
+) -> Result<()> {
+    let mut root_object = match memory.take(root)? {
+        Take::Free(root_object) => root_object,
+        Take::Busy(_) => unreachable!(),
+    };
+    root_object.downcast_mut::<RuntimeRoot>()?.roots = roots;
+    memory.fill(root, root_object)?;
+    Ok(())
+}
+
+fn allocate_in(
+    memory: &mut RawCollector<Slot<Object>>,
+    object: Object,
+    limit: Option<usize>,
+) -> Result<Address> {
+    if let Some(limit) = limit {
+        if memory.alive_count() >= limit {
+            memory.collect()?;
+        }
+        if memory.alive_count() >= limit {
+            return Err(Error::OutOfMemory {
+                live: memory.alive_count(),
+                capacity: limit,
+            });
+        }
+    }
+    memory.allocate(Slot::new(object)).map_err(|err| match err {
+        // the collector only gives up when all of its slots are alive after collecting
+        CollectorError::OutOfSlots => Error::OutOfMemory {
+            live: memory.alive_count(),
+            capacity: memory.alive_count(),
+        },
+        err => err.into(),
+    })
+}
+
+// where `load` finds an address: stack indices count from the top as everywhere else,
+// while arguments are numbered from the first one passed to `call`, so they don't shift
+// while the frame pushes its own values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Arg(usize),
+    Local(usize),
+    Parent(usize),
+    Context,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorStep {
+    // the yielded value is pushed
+    Yielded,
+    // with the values passed to `back`, if the generator returned through it
+    Done { pushed: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectorStats {
+    // including the runtime's own root and frames
+    pub allocated: usize,
+    // `None` unless told through `RuntimeBuilder::collector_capacity`
+    pub capacity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectReport {
+    pub reclaimed: usize,
+    pub alive: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
+    pub context: Address,
+    // `Class.method` for frames entered through `call_method` or `invoke`
+    pub method_name: Option<String>,
+    pub stack_len: usize,
+    pub depth: usize,
+}
+
+impl FrameInfo {
+    fn new(frame: &Frame, depth: usize) -> Self {
+        Self {
+            context: frame.context.to_owned(),
+            method_name: frame.method_name.to_owned(),
+            stack_len: frame.stack_len(),
+            depth,
+        }
+    }
+}
+
+impl std::fmt::Display for FrameInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.method_name, self.depth) {
+            (Some(name), _) => f.write_str(name),
+            (None, 0) => f.write_str("<root>"),
+            (None, _) => f.write_str("<method>"),
+        }
+    }
+}
+
+// temporaries live in the frame instead of the collector, and are dropped along with it
+// unless they get promoted by anything that needs their address
+#[derive(Clone)]
+enum StackEntry {
+    Heap(Address),
+    Temp(usize),
+    // an int without any object, promoted into a new `IntObject` like a temporary
+    Small(i64),
+}
+
+// how a small int is seen by typed accessors, which can only ask for an `IntObject`
+fn small_int_as<T: Any>(int: &mut IntObject) -> Result<&mut T> {
+    (int as &mut dyn Any)
+        .downcast_mut()
+        .ok_or(Error::TypeMismatch {
+            expected: std::any::type_name::<T>(),
+            actual: std::any::type_name::<IntObject>(),
+        })
+}
+
+// stands in for a temporary which is being promoted
+struct Placeholder;
+
+impl Keep for Placeholder {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+// fills the slots a runtime still had taken when it was dropped, and the slots of shared
+// sandbox arguments which could not be moved back
+struct AbandonedObject;
+
+impl Keep for AbandonedObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
 }
 
 struct Frame {
     context: Address,
-    address_stack: Vec<Address>,
+    stack: Vec<StackEntry>,
+    temps: Vec<Option<Object>>,
+    // the bottom of the stack, as pushed by the caller
+    argument_count: usize,
     parent: Option<Address>,
+    // a generator's frame, which its generator keeps between runs
+    generator: bool,
+    method_name: Option<String>,
+    // objects taken out through this frame and not filled back yet, which only this frame
+    // may fill
+    taken: HashSet<Address>,
+    // run in reverse order when the frame returns; the addresses they capture are only
+    // kept alive by the frame when passed to `defer_keeping`, until all of them have run
+    deferred: Vec<Deferred>,
+    deferred_keep: Vec<Address>,
 }
 
 impl Keep for Frame {
     fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
         f(&[self.context.to_owned()]);
-        f(&self.address_stack);
+        for entry in &self.stack {
+            if let StackEntry::Heap(address) = entry {
+                f(&[address.to_owned()]);
+            }
+        }
+        for temp in self.temps.iter().flatten() {
+            temp.with_keep(&mut f);
+        }
         if let Some(addr) = &self.parent {
             f(&[addr.to_owned()]);
         }
+        f(&self.deferred_keep);
     }
 }
 
@@ -39,178 +311,4204 @@ impl Frame {
     fn new(context: Address, parent: Option<Address>) -> Self {
         Self {
             context,
-            address_stack: Vec::new(),
+            stack: Vec::new(),
+            temps: Vec::new(),
+            argument_count: 0,
             parent,
+            generator: false,
+            method_name: None,
+            taken: HashSet::new(),
+            deferred: Vec::new(),
+            deferred_keep: Vec::new(),
         }
     }
 
+    // a pooled frame not in use, which keeps nothing but the root frame alive
+    fn idle(root_frame: Address) -> Self {
+        Self::new(root_frame, None)
+    }
+
     fn push_address(&mut self, address: Address) {
-        self.address_stack.push(address);
+        self.stack.push(StackEntry::Heap(address));
+    }
+
+    fn push_temp(&mut self, object: Object) {
+        self.temps.push(Some(object));
+        self.stack.push(StackEntry::Temp(self.temps.len() - 1));
+    }
+
+    fn push_small(&mut self, value: i64) {
+        self.stack.push(StackEntry::Small(value));
     }
 
-    fn pop_address(&mut self) -> Result<(), ShattuckError> {
-        self.address_stack
-            .pop()
-            .ok_or(ShattuckError::ExhaustedFrame)?;
+    fn pop_address(&mut self) -> Result<()> {
+        if let StackEntry::Temp(temp) = self.stack.pop().ok_or(Error::ExhaustedFrame)? {
+            self.remove_temp(temp);
+        }
         Ok(())
     }
 
-    fn get_address(&self, index: usize) -> Result<&Address, ShattuckError> {
-        if index == 0 || self.address_stack.len() < index {
-            return Err(ShattuckError::ExhaustedFrame);
+    fn remove_temp(&mut self, temp: usize) -> Object {
+        let object = self.temps[temp].take().unwrap();
+        while let Some(None) = self.temps.last() {
+            self.temps.pop();
+        }
+        object
+    }
+
+    fn get_entry(&self, index: usize) -> Result<&StackEntry> {
+        if index == 0 || self.stack.len() < index {
+            return Err(Error::ExhaustedFrame);
+        }
+        Ok(self.stack.get(self.stack.len() - index).unwrap())
+    }
+
+    fn get_entry_mut(&mut self, index: usize) -> Result<&mut StackEntry> {
+        if index == 0 || self.stack.len() < index {
+            return Err(Error::ExhaustedFrame);
         }
-        Ok(self
-            .address_stack
-            .get(self.address_stack.len() - index)
-            .unwrap())
+        let position = self.stack.len() - index;
+        Ok(&mut self.stack[position])
+    }
+
+    fn promote(&mut self, index: usize, address: Address) -> Object {
+        let position = self.stack.len() - index;
+        match std::mem::replace(&mut self.stack[position], StackEntry::Heap(address)) {
+            StackEntry::Temp(temp) => self.remove_temp(temp),
+            StackEntry::Small(value) => Object::with_vtable(IntObject(value)),
+            StackEntry::Heap(_) => unreachable!(),
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        while self.stack.len() > len {
+            self.pop_address().unwrap();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.stack.clear();
+        self.temps.clear();
     }
 
     fn stack_len(&self) -> usize {
-        self.address_stack.len()
+        self.stack.len()
+    }
+}
+
+// an object taken out for the lifetime of the handle and filled back on drop; it borrows
+// the runtime mutably so nothing else can reach the object through it meanwhile
+struct Handle<'a> {
+    memory: &'a Collector,
+    address: Address,
+    object: Option<Object>,
+}
+
+impl Handle<'_> {
+    fn object(&self) -> &Object {
+        self.object.as_ref().unwrap()
+    }
+
+    fn object_mut(&mut self) -> &mut Object {
+        self.object.as_mut().unwrap()
+    }
+}
+
+impl Drop for Handle<'_> {
+    fn drop(&mut self) {
+        let object = self.object.take().unwrap();
+        let key = holder_key(self.memory, &self.address);
+        HOLDERS.with(|holders| holders.borrow_mut().remove(&key));
+        self.memory.lock().fill(&self.address, object).unwrap();
+    }
+}
+
+pub struct RefHandle<'a, T: Any> {
+    handle: Handle<'a>,
+    marker: PhantomData<&'a T>,
+}
+
+impl<T: Any> Deref for RefHandle<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.handle.object.as_ref().unwrap().downcast_ref().unwrap()
+    }
+}
+
+pub struct MutHandle<'a, T: Any> {
+    handle: Handle<'a>,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<T: Any> Deref for MutHandle<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.handle.object.as_ref().unwrap().downcast_ref().unwrap()
+    }
+}
+
+impl<T: Any> DerefMut for MutHandle<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.handle.object.as_mut().unwrap().downcast_mut().unwrap()
+    }
+}
+
+// content of objects stays in place while they are moved in and out of the collector, and
+// a pinned object can't be moved out for good through `take`, `wait` or `replace`, so the
+// pointer is valid as long as the pin lives
+pub struct Pin {
+    pins: Pins,
+    address: Address,
+    pointer: *const dyn Any,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl Pin {
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    pub fn as_ptr<T: Any>(&self) -> Result<*const T> {
+        self.check::<T>()?;
+        Ok(self.pointer as *const T)
+    }
+
+    // nothing stops the object from being read or written through the runtime
+    // meanwhile, e.g. by `get_as` or another pin; the caller must make sure that the
+    // object is not accessed otherwise for as long as it writes through the pointer
+    pub fn as_mut_ptr<T: Any>(&self) -> Result<*mut T> {
+        self.check::<T>()?;
+        Ok(self.pointer as *const T as *mut T)
+    }
+
+    fn check<T: Any>(&self) -> Result<()> {
+        if self.type_id != TypeId::of::<T>() {
+            return Err(Error::TypeMismatch {
+                expected: std::any::type_name::<T>(),
+                actual: self.type_name,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Pin {
+    fn drop(&mut self) {
+        let mut pins = self.pins.lock();
+        let count = pins.get_mut(&self.address).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            pins.remove(&self.address);
+        }
+    }
+}
+
+// a runtime put aside between two calls; its frames stay rooted in the collector, so
+// other runtimes sharing it can keep collecting meanwhile
+pub struct SuspendedRuntime {
+    runtime: Runtime,
+}
+
+impl SuspendedRuntime {
+    pub fn depth(&self) -> usize {
+        self.runtime.frame_stack.len()
     }
 }
 
 pub struct RuntimeBuilder {
     collector: Collector,
-    frame_object: Frame,
+    // nil when not given
+    context: Option<Address>,
+    scheduler: Arc<dyn Scheduler + Send + Sync>,
+    cancellation: CancellationToken,
+    frame_pool_size: usize,
+    allocation_limit: Option<usize>,
+    max_depth: Option<usize>,
+    suites: Suites,
+    install: Vec<String>,
+    collector_capacity: Option<usize>,
+    profiling: bool,
+    stdout: Box<dyn Write + Send>,
+    numeric_mode: NumericMode,
 }
 
 impl RuntimeBuilder {
     pub fn new(collector: Collector, context: Address) -> Self {
-        let frame_object = Frame::new(context, None);
+        Self {
+            context: Some(context),
+            ..Self::new_with_nil_context(collector)
+        }
+    }
+
+    pub fn new_with_nil_context(collector: Collector) -> Self {
         Self {
             collector,
-            frame_object,
+            context: None,
+            scheduler: Arc::new(NoopScheduler),
+            cancellation: CancellationToken::new(),
+            allocation_limit: None,
+            max_depth: None,
+            suites: builtin_suites(),
+            install: Vec::new(),
+            collector_capacity: None,
+            profiling: false,
+            stdout: Box::new(io::stdout()),
+            frame_pool_size: 0,
+            numeric_mode: NumericMode::default(),
         }
     }
 
-    pub fn boot(self) -> Result<Runtime, Error> {
-        let frame = self
-            .collector
-            .lock()
-            .allocate(Slot::new(Object::new(self.frame_object)))?;
-        Ok(Runtime {
+    pub fn frame_pool_size(mut self, size: usize) -> Self {
+        self.frame_pool_size = size;
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    // objects other than frames can only be allocated while fewer than `limit` objects
+    // are alive, the slots above are reserved so that calls keep working
+    pub fn allocation_limit(mut self, limit: usize) -> Self {
+        self.allocation_limit = Some(limit);
+        self
+    }
+
+    // the number of frames, including the root one
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    // what int arithmetic of the built-in methods does on overflow
+    pub fn numeric_mode(mut self, mode: NumericMode) -> Self {
+        self.numeric_mode = mode;
+        self
+    }
+
+    // the capacity the collector was created with, for `memory_stats`; hulunbuir has no
+    // way to tell
+    pub fn collector_capacity(mut self, capacity: usize) -> Self {
+        self.collector_capacity = Some(capacity);
+        self
+    }
+
+    // makes a suite available to `install`, replacing any built-in one of the same name
+    pub fn suite(mut self, suite: Arc<dyn BuiltinSuite>) -> Self {
+        self.suites.insert(suite.name().to_owned(), suite);
+        self
+    }
+
+    // installed along with their dependencies while booting
+    pub fn install(mut self, names: &[&str]) -> Self {
+        self.install
+            .extend(names.iter().map(|name| (*name).to_owned()));
+        self
+    }
+
+    // see `Runtime::profile_report`
+    pub fn enable_profiling(mut self, enabled: bool) -> Self {
+        self.profiling = enabled;
+        self
+    }
+
+    pub fn stdout(mut self, stdout: Box<dyn Write + Send>) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    pub fn with_scheduler(mut self, scheduler: Arc<dyn Scheduler + Send + Sync>) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    pub fn boot(self) -> Result<Runtime> {
+        let mut memory = self.collector.lock();
+        let root_object = RuntimeRoot {
+            roots: Vec::new(),
+            previous: memory.root().to_owned(),
+        };
+        let root = allocate_in(&mut memory, Object::new(root_object), None)?;
+        memory.set_root(root.to_owned());
+        let nil = allocate_in(&mut memory, Object::with_vtable(NilObject::new()), None)?;
+        refresh_root(&mut memory, &root, vec![nil.to_owned()])?;
+        let context = self.context.unwrap_or_else(|| nil.to_owned());
+        let frame_object = Frame::new(context, None);
+        let frame = allocate_in(&mut memory, Object::new(frame_object), None)?;
+        refresh_root(&mut memory, &root, vec![frame.to_owned(), nil.to_owned()])?;
+        let mut frame_pool = Vec::with_capacity(self.frame_pool_size);
+        for _ in 0..self.frame_pool_size {
+            let idle = Frame::idle(frame.to_owned());
+            frame_pool.push(allocate_in(&mut memory, Object::new(idle), None)?);
+            let mut roots = frame_pool.to_owned();
+            roots.push(frame.to_owned());
+            roots.push(nil.to_owned());
+            refresh_root(&mut memory, &root, roots)?;
+        }
+        drop(memory);
+        let mut runtime = Runtime {
+            id: NEXT_RUNTIME_ID.fetch_add(1, Ordering::Relaxed),
             memory: self.collector,
             frame_stack: vec![frame],
-        })
+            frame_cache: None,
+            frame_hold: 0,
+            scheduler: self.scheduler,
+            cancellation: self.cancellation,
+            frame_pool,
+            frame_pool_size: self.frame_pool_size,
+            pending_frame: None,
+            taken: HashSet::new(),
+            root,
+            pins: Pins::default(),
+            view_handle: None,
+            allocation_limit: self.allocation_limit,
+            max_depth: self.max_depth,
+            generator_yields: Vec::new(),
+            globals: HashMap::new(),
+            suites: self.suites,
+            installed: Vec::new(),
+            collector_capacity: self.collector_capacity,
+            nil,
+            profiler: if self.profiling {
+                Some(Profiler::default())
+            } else {
+                None
+            },
+            stdout: self.stdout,
+            numeric_mode: self.numeric_mode,
+        };
+        let install: Vec<&str> = self.install.iter().map(String::as_str).collect();
+        runtime.install(&install)?;
+        Ok(runtime)
     }
 }
 
 impl Runtime {
-    pub fn push(&mut self, object: Object) -> Result<(), Error> {
-        let addr = self.memory.lock().allocate(Slot::new(object))?;
-        self.with_current_frame_mut(|frame| frame.push_address(addr));
+    #[deprecated(note = "use `push_new`, or `push_address` for an existing object")]
+    pub fn push(&mut self, object: Object) -> Result<()> {
+        self.push_new(object).map(drop)
+    }
+
+    // the address is returned so that the caller can keep it as well
+    pub fn push_new(&mut self, object: Object) -> Result<Address> {
+        let addr = self.allocate(object)?;
+        self.with_current_frame_mut(|frame| frame.push_address(addr.to_owned()));
+        Ok(addr)
+    }
+
+    // fails for an address which has no object (left) in the collector
+    pub fn push_address(&mut self, address: Address) -> Result<()> {
+        let mut memory = self.memory.lock();
+        if let Take::Free(object) = memory.take(&address)? {
+            memory.fill(&address, object)?;
+        }
+        drop(memory);
+        self.with_current_frame_mut(|frame| frame.push_address(address));
+        Ok(())
+    }
+
+    pub fn push_temp(&mut self, object: Object) -> Result<()> {
+        self.with_current_frame_mut(|frame| frame.push_temp(object));
         Ok(())
     }
 
-    pub fn pop(&mut self) -> Result<(), Error> {
-        self.with_current_frame_mut(|frame| frame.pop_address().map_err(Into::into))
+    pub fn pop(&mut self) -> Result<()> {
+        self.with_current_frame_mut(|frame| frame.pop_address())
+    }
+
+    // a heap object stays taken for good, so this is only for runtimes about to be dropped
+    fn pop_object(&mut self) -> Result<Object> {
+        let object = match self.get_entry(1)? {
+            StackEntry::Temp(_) => {
+                return self.with_current_frame_mut(|frame| match frame.stack.pop() {
+                    Some(StackEntry::Temp(temp)) => Ok(frame.remove_temp(temp)),
+                    _ => unreachable!(),
+                })
+            }
+            StackEntry::Small(value) => Object::with_vtable(IntObject(value)),
+            StackEntry::Heap(address) => {
+                self.check_unpinned(&address)?;
+                self.wait_object(&address)?
+            }
+        };
+        self.pop()?;
+        Ok(object)
     }
 
-    pub fn take(&mut self, index: usize) -> Result<Object, Error> {
-        let addr = self.clone_address(index)?;
-        match self.memory.lock().take(&addr)? {
-            Take::Free(object) => Ok(object),
-            Take::Busy(_) => Err(ShattuckError::BusyObject.into()),
+    pub fn take(&mut self, index: usize) -> Result<Object> {
+        let addr = self.address_of(index)?;
+        self.check_unpinned(&addr)?;
+        self.scheduler.yield_point("take");
+        let take = self.memory.lock().take(&addr)?;
+        match take {
+            Take::Free(object) => {
+                self.hold(&addr, &object);
+                self.taken.insert(addr.to_owned());
+                self.with_current_frame_mut(|frame| frame.taken.insert(addr));
+                Ok(object)
+            }
+            Take::Busy(_) => Err(Error::BusyObject),
         }
     }
 
-    pub fn wait(&mut self, index: usize) -> Result<Object, Error> {
-        let addr = self.clone_address(index)?;
-        self.wait_object(&addr)
+    pub fn wait(&mut self, index: usize) -> Result<Object> {
+        let addr = self.address_of(index)?;
+        self.check_unpinned(&addr)?;
+        let object = self.wait_object(&addr)?;
+        self.hold(&addr, &object);
+        self.taken.insert(addr.to_owned());
+        self.with_current_frame_mut(|frame| frame.taken.insert(addr));
+        Ok(object)
     }
 
-    pub fn fill(&mut self, index: usize, object: Object) -> Result<(), Error> {
-        let addr = self.clone_address(index)?;
-        self.memory.lock().fill(&addr, object).map_err(Into::into)
+    // what dropping does as well; returns how many objects were still taken through `take`
+    // or `wait`, which now fail with `Abandoned` for everyone waiting on them
+    pub fn abandon(mut self) -> usize {
+        self.abandon_takes()
     }
 
-    fn clone_address(&mut self, index: usize) -> Result<Address, Error> {
-        self.with_current_frame_mut(|frame| {
-            frame
-                .get_address(index)
-                .map(ToOwned::to_owned)
-                .map_err(Into::into)
+    fn abandon_takes(&mut self) -> usize {
+        let mut memory = self.memory.lock();
+        // not `store_frame`, which would panic while unwinding from a panic
+        if let Some(frame_object) = self.frame_cache.take() {
+            let frame = self.frame_stack.last().unwrap();
+            let _ = memory.fill(frame, Object::new(frame_object));
+        }
+        let mut abandoned = 0;
+        for address in self.taken.drain() {
+            // it may have been filled back around the runtime, which `fill` would overwrite
+            match memory.take(&address) {
+                Ok(Take::Free(object)) => {
+                    let _ = memory.fill(&address, object);
+                }
+                Ok(Take::Busy(_)) => {
+                    let _ = memory.fill(&address, Object::new(AbandonedObject));
+                    abandoned += 1;
+                }
+                Err(_) => {}
+            }
+        }
+        let id = self.id;
+        HOLDERS.with(|holders| {
+            holders
+                .borrow_mut()
+                .retain(|_, holder| holder.runtime != id)
+        });
+        abandoned
+    }
+
+    fn holder_key(&self, address: &Address) -> (usize, Address) {
+        holder_key(&self.memory, address)
+    }
+
+    fn hold(&self, address: &Address, object: &Object) {
+        let holder = Holder {
+            runtime: self.id,
+            type_name: object.type_name(),
+        };
+        let key = self.holder_key(address);
+        HOLDERS.with(|holders| holders.borrow_mut().insert(key, holder));
+    }
+
+    fn release(&self, address: &Address) {
+        let key = self.holder_key(address);
+        HOLDERS.with(|holders| holders.borrow_mut().remove(&key));
+    }
+
+    // only consulted once the object turned out busy
+    fn check_deadlock(&self, address: &Address) -> Result<()> {
+        let key = self.holder_key(address);
+        HOLDERS.with(|holders| match holders.borrow().get(&key) {
+            Some(holder) => Err(Error::DeadlockDetected {
+                cycle: vec![
+                    format!("runtime {} waits for {:?}", self.id, address),
+                    format!(
+                        "{:?} <{}> is taken by runtime {} on the same thread",
+                        address, holder.type_name, holder.runtime
+                    ),
+                ],
+            }),
+            None => Ok(()),
         })
     }
 
-    fn wait_object(&self, address: &Address) -> Result<Object, Error> {
-        loop {
-            let take = self.memory.lock().take(address)?;
-            match take {
-                Take::Free(object) => return Ok(object),
-                Take::Busy(parker) => parker.park(),
+    pub fn fill(&mut self, index: usize, object: Object) -> Result<()> {
+        let addr = self.address_of(index)?;
+        if !self.with_current_frame_mut(|frame| frame.taken.remove(&addr)) {
+            return Err(Error::FillWithoutTake);
+        }
+        self.taken.remove(&addr);
+        self.scheduler.yield_point("fill");
+        self.release(&addr);
+        Ok(self.memory.lock().fill(&addr, object)?)
+    }
+
+    pub fn replace(&mut self, index: usize, object: Object) -> Result<Object> {
+        let addr = self.address_of(index)?;
+        self.check_unpinned(&addr)?;
+        let mut memory = self.memory.lock();
+        match memory.take(&addr)? {
+            Take::Free(original) => {
+                memory.fill(&addr, object)?;
+                Ok(original)
             }
+            Take::Busy(_) => Err(Error::BusyObject),
         }
     }
 
-    fn with_current_frame_ref<F, R>(&self, f: F) -> R
+    // whoever gets a pinned object out could drop it under the pin's pointer
+    fn check_unpinned(&self, address: &Address) -> Result<()> {
+        if self.pins.lock().contains_key(address) {
+            return Err(Error::Pinned);
+        }
+        Ok(())
+    }
+
+    fn get_entry(&self, index: usize) -> Result<StackEntry> {
+        self.with_current_frame_ref(|frame| frame.get_entry(index).map(ToOwned::to_owned))
+    }
+
+    // promotes a temporary into the collector, since whoever asks for its address may
+    // outlive the frame
+    fn address_of(&mut self, index: usize) -> Result<Address> {
+        if let StackEntry::Heap(address) = self.get_entry(index)? {
+            return Ok(address);
+        }
+        // the temporary stays in the frame while allocating, keeping its children alive
+        let address = self.allocate(Object::new(Placeholder))?;
+        let object = self.with_current_frame_mut(|frame| frame.promote(index, address.to_owned()));
+        self.memory.lock().replace(&address, Slot::new(object))?;
+        Ok(address)
+    }
+
+    fn read_object<T: Any, F, R>(&self, index: usize, f: F) -> Result<R>
     where
-        F: FnOnce(&Frame) -> R,
+        F: FnOnce(&T) -> R,
     {
-        let frame = self.frame_stack.last().unwrap().to_owned();
-        let mut frame_object = self.wait_object(&frame).unwrap().downcast().unwrap();
-        let result = f(&mut frame_object);
-        self.memory
-            .lock()
-            .fill(&frame, Object::new(frame_object))
-            .unwrap();
-        result
+        self.read_at(index, |object: &T| Ok(f(object)))
     }
 
-    fn with_current_frame_mut<F, R>(&mut self, f: F) -> R
+    // the frame is visited once for both the entry and the object, and a temporary is
+    // read in place instead of being promoted
+    pub fn read_at<T: Any, R, F>(&self, index: usize, f: F) -> Result<R>
     where
-        F: FnOnce(&mut Frame) -> R,
+        F: FnOnce(&T) -> Result<R>,
     {
-        let frame = self.frame_stack.last().unwrap().to_owned();
-        let mut frame_object = self.wait_object(&frame).unwrap().downcast().unwrap();
-        let result = f(&mut frame_object);
-        self.memory
-            .lock()
-            .fill(&frame, Object::new(frame_object))
-            .unwrap();
-        result
+        self.with_current_frame_ref(|frame| match frame.get_entry(index)? {
+            StackEntry::Temp(temp) => f(frame.temps[*temp].as_ref().unwrap().downcast_ref()?),
+            StackEntry::Small(value) => f(small_int_as(&mut IntObject(*value))?),
+            StackEntry::Heap(address) => {
+                f(self.address_handle(address)?.object().downcast_ref()?)
+            }
+        })
     }
 
-    pub fn stack_len(&self) -> usize {
-        self.with_current_frame_ref(|frame| frame.stack_len())
+    pub fn write_at<T: Any, R, F>(&mut self, index: usize, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut T) -> Result<R>,
+    {
+        self.hold_frame(|runtime| {
+            let frame = runtime.frame_cache.as_mut().unwrap();
+            let address = match frame.get_entry_mut(index)? {
+                StackEntry::Temp(temp) => {
+                    let temp = *temp;
+                    return f(frame.temps[temp].as_mut().unwrap().downcast_mut()?);
+                }
+                StackEntry::Small(value) => {
+                    let mut int = IntObject(*value);
+                    let result = f(small_int_as(&mut int)?);
+                    *value = int.0;
+                    return result;
+                }
+                StackEntry::Heap(address) => address.to_owned(),
+            };
+            f(runtime
+                .address_handle(&address)?
+                .object_mut()
+                .downcast_mut()?)
+        })
     }
 
-    pub fn call(&mut self, context: usize, arguments: &[usize]) -> Result<(), Error> {
-        let caller_frame = self.frame_stack.last().unwrap().to_owned();
-        let callee_frame_object =
-            self.with_current_frame_ref::<_, Result<_, Error>>(|caller_frame_object| {
-                let context = caller_frame_object.get_address(context)?.to_owned();
-                let mut frame = Frame::new(context, Some(caller_frame.clone()));
-                for arg in arguments.iter().rev() {
-                    let addr = caller_frame_object.get_address(*arg)?.to_owned();
-                    frame.push_address(addr);
-                }
-                Ok(frame)
-            })?;
-        let callee_frame = self
-            .memory
-            .lock()
-            .allocate(Slot::new(Object::new(callee_frame_object)))?;
-        self.frame_stack.push(callee_frame);
-        Ok(())
+    fn read_address<T: Any, F, R>(&self, addr: &Address, f: F) -> Result<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.address_handle(addr)?.object().downcast_ref().map(f)
     }
 
-    pub fn back(&mut self, returned: &[usize]) -> Result<(), Error> {
-        if self.frame_stack.len() == 1 {
-            return Err(ShattuckError::NoParentFrame.into());
-        }
-        let callee_frame = self.frame_stack.last().unwrap();
-        let callee_frame_object: Frame =
-            self.wait_object(callee_frame).unwrap().downcast().unwrap();
-        self.frame_stack.pop().unwrap();
-        self.with_current_frame_mut::<_, Result<_, Error>>(|caller_frame_object| {
-            for ret in returned.iter().rev() {
-                let addr = callee_frame_object.get_address(*ret)?.to_owned();
-                caller_frame_object.push_address(addr);
-            }
-            Ok(())
-        })?;
-        Ok(())
+    fn handle<T: Any>(&mut self, index: usize) -> Result<Handle<'_>> {
+        let address = self.address_of(index)?;
+        let handle = self.address_handle(&address)?;
+        // dropping the handle on mismatch fills the object back
+        handle.object().downcast_ref::<T>()?;
+        Ok(handle)
+    }
+
+    // the object counts as held by this runtime until the handle drops
+    fn address_handle(&self, address: &Address) -> Result<Handle<'_>> {
+        let object = self.wait_object(address)?;
+        self.hold(address, &object);
+        Ok(Handle {
+            memory: &self.memory,
+            address: address.to_owned(),
+            object: Some(object),
+        })
+    }
+
+    pub fn get_as<T: Any>(&mut self, index: usize) -> Result<RefHandle<'_, T>> {
+        Ok(RefHandle {
+            handle: self.handle::<T>(index)?,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn get_as_mut<T: Any>(&mut self, index: usize) -> Result<MutHandle<'_, T>> {
+        Ok(MutHandle {
+            handle: self.handle::<T>(index)?,
+            marker: PhantomData,
+        })
+    }
+
+    // the object is filled back however `f` ends, even by panicking
+    pub fn with_object_mut<T: Any, R, F>(&mut self, index: usize, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut T) -> Result<R>,
+    {
+        f(&mut *self.get_as_mut::<T>(index)?)
+    }
+
+    // takes the two objects in the same order whichever index comes first, so that two
+    // callers passing them the other way around don't wait for each other
+    pub fn with_two_objects_mut<A: Any, B: Any, R, F>(
+        &mut self,
+        first: usize,
+        second: usize,
+        f: F,
+    ) -> Result<R>
+    where
+        F: FnOnce(&mut A, &mut B) -> Result<R>,
+    {
+        let first = self.address_of(first)?;
+        let second = self.address_of(second)?;
+        if first == second {
+            return Err(Error::BusyObject);
+        }
+        let swapped = address_key(&first) > address_key(&second);
+        let (early, late) = if swapped {
+            (&second, &first)
+        } else {
+            (&first, &second)
+        };
+        let mut early = self.address_handle(early)?;
+        let mut late = self.address_handle(late)?;
+        let (a, b) = if swapped {
+            (&mut late, &mut early)
+        } else {
+            (&mut early, &mut late)
+        };
+        let a = a.object.as_mut().unwrap().downcast_mut()?;
+        let b = b.object.as_mut().unwrap().downcast_mut()?;
+        f(a, b)
+    }
+
+    pub fn hash_of(&mut self, index: usize) -> Result<u64> {
+        let address = self.address_of(index)?;
+        self.address_handle(&address)?.object().hash_value()
+    }
+
+    pub fn compare(&mut self, first: usize, second: usize) -> Result<CmpOrdering> {
+        let first = self.address_of(first)?;
+        let second = self.address_of(second)?;
+        let first = self.address_handle(&first)?;
+        if first.address == second {
+            return first.object().compare(first.object());
+        }
+        let second = self.address_handle(&second)?;
+        first.object().compare(second.object())
+    }
+
+    pub fn sort_list(&mut self, index: usize) -> Result<()> {
+        self.sort_list_by(index, Object::compare)
+    }
+
+    // stable; the list is left as it was if `compare` fails for any pair
+    pub fn sort_list_by<F>(&mut self, index: usize, mut compare: F) -> Result<()>
+    where
+        F: FnMut(&Object, &Object) -> Result<CmpOrdering>,
+    {
+        let address = self.address_of(index)?;
+        let mut list = self.address_handle(&address)?;
+        let mut items = list.object().downcast_ref::<ListObject>()?.items().to_vec();
+        // every item is taken once, however often it appears in the list
+        let mut objects = HashMap::new();
+        for item in &items {
+            if *item == address {
+                return Err(Error::BusyObject);
+            }
+            if !objects.contains_key(item) {
+                objects.insert(item.to_owned(), self.address_handle(item)?);
+            }
+        }
+        let mut failure = None;
+        items.sort_by(|first, second| {
+            if failure.is_some() {
+                return CmpOrdering::Equal;
+            }
+            compare(objects[first].object(), objects[second].object()).unwrap_or_else(|err| {
+                failure = Some(err);
+                CmpOrdering::Equal
+            })
+        });
+        if let Some(err) = failure {
+            return Err(err);
+        }
+        list.object_mut()
+            .downcast_mut::<ListObject>()?
+            .set_items(items);
+        Ok(())
+    }
+
+    pub fn dict_insert(&mut self, dict: usize, key: usize, value: usize) -> Result<()> {
+        let dict = self.address_of(dict)?;
+        let key = self.address_of(key)?;
+        let value = self.address_of(value)?;
+        if dict == key {
+            return Err(Error::BusyObject);
+        }
+        let key_object = self.address_handle(&key)?;
+        let hash = key_object.object().hash_value()?;
+        let mut dict_object = self.address_handle(&dict)?;
+        dict_object
+            .object_mut()
+            .downcast_mut::<ObjectDict>()?
+            .insert_with(hash, key.to_owned(), value, |stored| {
+                self.keys_equal(stored, &key_object)
+            })?;
+        Ok(())
+    }
+
+    // pushes the value and returns true if the key is in the dictionary
+    pub fn dict_get(&mut self, dict: usize, key: usize) -> Result<bool> {
+        let dict = self.address_of(dict)?;
+        let key = self.address_of(key)?;
+        if dict == key {
+            return Err(Error::BusyObject);
+        }
+        let key_object = self.address_handle(&key)?;
+        let hash = key_object.object().hash_value()?;
+        let dict_object = self.address_handle(&dict)?;
+        let value = dict_object
+            .object()
+            .downcast_ref::<ObjectDict>()?
+            .get_with(hash, |stored| self.keys_equal(stored, &key_object))?
+            .cloned();
+        drop((key_object, dict_object));
+        match value {
+            Some(value) => {
+                self.with_current_frame_mut(|frame| frame.push_address(value));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // keys of types which don't compare with each other are just different
+    fn keys_equal(&self, stored: &Address, key: &Handle) -> Result<bool> {
+        if *stored == key.address {
+            return Ok(true);
+        }
+        let stored = self.address_handle(stored)?;
+        Ok(stored.object().compare(key.object()).ok() == Some(CmpOrdering::Equal))
+    }
+
+    // suites installed before are skipped, so installing is idempotent
+    pub fn install(&mut self, names: &[&str]) -> Result<()> {
+        for suite in suite::resolve(&self.suites, names)? {
+            if self.installed.iter().any(|name| name == suite.name()) {
+                continue;
+            }
+            suite.install(self)?;
+            self.installed.push(suite.name().to_owned());
+        }
+        Ok(())
+    }
+
+    // in installation order
+    pub fn installed_suites(&self) -> &[String] {
+        &self.installed
+    }
+
+    pub fn set_global(&mut self, name: &str, index: usize) -> Result<()> {
+        let address = self.address_of(index)?;
+        self.globals.insert(name.to_owned(), address);
+        self.refresh_roots();
+        Ok(())
+    }
+
+    pub fn push_global(&mut self, name: &str) -> Result<()> {
+        let address = self
+            .globals
+            .get(name)
+            .ok_or_else(|| Error::NoSuchGlobal(name.to_owned()))?
+            .to_owned();
+        self.with_current_frame_mut(|frame| frame.push_address(address));
+        Ok(())
+    }
+
+    pub fn push_int(&mut self, value: i64) -> Result<()> {
+        self.push_new(Object::with_vtable(IntObject(value)))
+            .map(drop)
+    }
+
+    // pushes no object at all; a distinct `IntObject` is created for it only once its
+    // address is needed, e.g. as an argument or context of a call, so that two small ints
+    // are never the same object
+    pub fn push_small(&mut self, value: i64) -> Result<()> {
+        self.with_current_frame_mut(|frame| frame.push_small(value));
+        Ok(())
+    }
+
+    // what passing a small int or a temporary anywhere by address does anyway
+    pub fn materialize(&mut self, index: usize) -> Result<()> {
+        self.address_of(index).map(drop)
+    }
+
+    pub fn get_int(&self, index: usize) -> Result<i64> {
+        self.read_object(index, |int: &IntObject| int.0)
+    }
+
+    // atomic operations go through the scheduler, so that a recorded run can be replayed
+    // with the same order of them
+    fn atomic<F, R>(&self, index: usize, tag: &str, f: F) -> Result<R>
+    where
+        F: FnOnce(&AtomicIntObject) -> R,
+    {
+        let atomic = self.read_object(index, |atomic: &AtomicIntObject| atomic.to_owned())?;
+        let mut f = Some(f);
+        let mut result = None;
+        self.scheduler.atomic_point(tag, &mut || {
+            result = f.take().map(|f| f(&atomic));
+        });
+        Ok(result.unwrap())
+    }
+
+    pub fn atomic_load(&self, index: usize) -> Result<i64> {
+        self.atomic(index, "atomic_load", AtomicIntObject::load)
+    }
+
+    pub fn atomic_store(&self, index: usize, value: i64) -> Result<()> {
+        self.atomic(index, "atomic_store", |atomic| atomic.store(value))
+    }
+
+    pub fn atomic_fetch_add(&self, index: usize, value: i64) -> Result<i64> {
+        self.atomic(index, "atomic_fetch_add", |atomic| atomic.fetch_add(value))
+    }
+
+    pub fn atomic_fetch_sub(&self, index: usize, value: i64) -> Result<i64> {
+        self.atomic(index, "atomic_fetch_sub", |atomic| atomic.fetch_sub(value))
+    }
+
+    pub fn atomic_compare_exchange(
+        &self,
+        index: usize,
+        expected: i64,
+        new: i64,
+    ) -> Result<std::result::Result<i64, i64>> {
+        self.atomic(index, "atomic_compare_exchange", |atomic| {
+            atomic.compare_exchange(expected, new)
+        })
+    }
+
+    // the clock is the scheduler's, which may be a virtual one
+    pub fn now_millis(&mut self) -> Result<()> {
+        let now = self.scheduler.now();
+        self.push_int(now.as_millis() as i64)
+    }
+
+    // goes through the scheduler, which sleeps for real unless it has a better way
+    pub fn yield_sleep(&mut self, duration: Duration) -> Result<()> {
+        self.cancellation.check()?;
+        self.scheduler.yield_point("sleep");
+        self.scheduler.sleep(duration);
+        Ok(())
+    }
+
+    // negative durations don't sleep at all
+    pub fn sleep_millis(&mut self, millis: usize) -> Result<()> {
+        let millis = self.get_int(millis)?.max(0) as u64;
+        self.yield_sleep(Duration::from_millis(millis))
+    }
+
+    pub fn deadline(&mut self, millis: usize) -> Result<()> {
+        let millis = self.get_int(millis)?.max(0) as u64;
+        let deadline = DeadlineObject::new(self.scheduler.now(), Duration::from_millis(millis));
+        self.push_new(Object::with_vtable(deadline)).map(drop)
+    }
+
+    pub fn expired(&self, deadline: usize) -> Result<bool> {
+        let now = self.scheduler.now();
+        self.read_object(deadline, |deadline: &DeadlineObject| deadline.expired(now))
+    }
+
+    pub fn start_stopwatch(&mut self) -> Result<()> {
+        let stopwatch = StopwatchObject::start(self.scheduler.now());
+        self.push_new(Object::with_vtable(stopwatch)).map(drop)
+    }
+
+    pub fn elapsed_millis(&mut self, stopwatch: usize) -> Result<()> {
+        let now = self.scheduler.now();
+        let elapsed = self.read_object(stopwatch, |stopwatch: &StopwatchObject| {
+            stopwatch.elapsed(now)
+        })?;
+        self.push_int(elapsed.as_millis() as i64)
+    }
+
+    pub fn push_str(&mut self, value: &str) -> Result<()> {
+        self.push_new(Object::with_vtable(StringObject(value.to_owned())))
+            .map(drop)
+    }
+
+    pub fn get_str(&self, index: usize) -> Result<String> {
+        self.read_object(index, |string: &StringObject| string.0.to_owned())
+    }
+
+    pub fn push_float(&mut self, value: f64) -> Result<()> {
+        self.push_new(Object::with_vtable(FloatObject(value)))
+            .map(drop)
+    }
+
+    pub fn get_float(&self, index: usize) -> Result<f64> {
+        self.read_object(index, |float: &FloatObject| float.0)
+    }
+
+    pub fn numeric_mode(&self) -> NumericMode {
+        self.numeric_mode
+    }
+
+    // an int or a float
+    pub fn get_number(&self, index: usize) -> Result<Number> {
+        match self.get_int(index) {
+            Err(Error::TypeMismatch { .. }) => self.get_float(index).map(Number::Float),
+            int => int.map(Number::Int),
+        }
+    }
+
+    pub fn push_number(&mut self, number: Number) -> Result<()> {
+        match number {
+            Number::Int(value) => self.push_int(value),
+            Number::Float(value) => self.push_float(value),
+        }
+    }
+
+    pub fn int_to_string(&mut self, index: usize) -> Result<()> {
+        let string = self.read_object(index, |int: &IntObject| int.to_string())?;
+        self.push_str(&string)
+    }
+
+    pub fn int_to_float(&mut self, index: usize) -> Result<()> {
+        let float = self.read_object(index, IntObject::to_float)?;
+        self.push_new(Object::with_vtable(float)).map(drop)
+    }
+
+    pub fn float_to_string(&mut self, index: usize, precision: Option<usize>) -> Result<()> {
+        let string =
+            self.read_object(index, |float: &FloatObject| float.to_string_with(precision))?;
+        self.push_str(&string)
+    }
+
+    pub fn float_to_int(&mut self, index: usize, mode: RoundMode) -> Result<()> {
+        let int = self.read_object(index, |float: &FloatObject| float.to_int(mode))??;
+        self.push_new(Object::with_vtable(int)).map(drop)
+    }
+
+    // pushes an ok result with the int, or an err result with the message of the failure
+    pub fn parse_int(&mut self, index: usize) -> Result<()> {
+        let parsed = self.read_object(index, |string: &StringObject| string.parse_int())?;
+        self.push_parsed(parsed)
+    }
+
+    pub fn parse_float(&mut self, index: usize) -> Result<()> {
+        let parsed = self.read_object(index, |string: &StringObject| string.parse_float())?;
+        self.push_parsed(parsed)
+    }
+
+    fn push_parsed<T: HasVTable>(&mut self, parsed: Result<T>) -> Result<()> {
+        let pushed = match parsed {
+            Ok(value) => {
+                self.push_new(Object::with_vtable(value))?;
+                self.push_ok(1)
+            }
+            Err(err) => {
+                self.push_str(&err.to_string())?;
+                self.push_err(1)
+            }
+        };
+        if let Err(err) = pushed {
+            self.pop()?;
+            return Err(err);
+        }
+        // leaves the result alone on top, in place of the payload
+        let stack_len = self.stack_len();
+        self.with_current_frame_mut(|frame| {
+            frame.stack.swap(stack_len - 1, stack_len - 2);
+            frame.pop_address()
+        })
+    }
+
+    pub fn dump_graph(&mut self, index: usize) -> Result<String> {
+        let address = self.address_of(index)?;
+        walk::dump_graph(&self.memory, address)
+    }
+
+    // never waits for an argument, which is rendered busy instead
+    fn render(&self, index: usize) -> Result<String> {
+        let addr = match self.get_entry(index)? {
+            StackEntry::Heap(addr) => addr,
+            StackEntry::Temp(temp) => {
+                return Ok(self.with_current_frame_ref(|frame| {
+                    fmt::render(frame.temps[temp].as_ref().unwrap())
+                }))
+            }
+            StackEntry::Small(value) => {
+                return Ok(fmt::render(&Object::with_vtable(IntObject(value))))
+            }
+        };
+        let mut memory = self.memory.lock();
+        match memory.take(&addr)? {
+            Take::Free(object) => {
+                let rendered = fmt::render(&object);
+                memory.fill(&addr, object)?;
+                Ok(rendered)
+            }
+            Take::Busy(_) => Ok(BUSY.to_owned()),
+        }
+    }
+
+    fn format_string(&self, template: usize, arguments: &[usize]) -> Result<String> {
+        let template = self.get_str(template)?;
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.render(*argument))
+            .collect::<Result<Vec<_>>>()?;
+        fmt::format(&template, &arguments)
+    }
+
+    pub fn format(&mut self, template: usize, arguments: &[usize]) -> Result<()> {
+        let formatted = self.format_string(template, arguments)?;
+        self.push_new(Object::with_vtable(StringObject(formatted)))
+            .map(drop)
+    }
+
+    pub fn print(&mut self, template: usize, arguments: &[usize]) -> Result<()> {
+        let formatted = self.format_string(template, arguments)?;
+        self.stdout.write_all(formatted.as_bytes())?;
+        Ok(self.stdout.flush()?)
+    }
+
+    // the current frame is not checked while it is held
+    pub fn validate(&self) -> Result<Vec<walk::HeapViolation>> {
+        walk::validate(&self.memory, self.root.to_owned())
+    }
+
+    // the objects this runtime keeps alive, walked from its root; like `validate`, the
+    // current frame's children are skipped while it is held
+    pub fn heap_entries(&self) -> Result<Vec<walk::HeapEntry>> {
+        walk::entries(&self.memory, self.root.to_owned())
+    }
+
+    pub fn reachable_count(&self) -> Result<usize> {
+        walk::reachable_from(&self.memory, self.root.to_owned())
+    }
+
+    // what keeps the object at `index` alive, as seen from the root
+    pub fn find_referrers(&mut self, index: usize) -> Result<Vec<Address>> {
+        let address = self.address_of(index)?;
+        walk::find_referrers(&self.memory, self.root.to_owned(), &address)
+    }
+
+    // looks through the parents of the class in turn, the first one which has the method
+    // wins; a miss names the class at `class`
+    pub fn get_method(&self, class: usize, name: &str) -> Result<Address> {
+        let (found, mut parent) = self.read_object(class, |class: &ClassObject| {
+            let found = class.get_method(name).map(ToOwned::to_owned);
+            (found, class.parent().cloned())
+        })?;
+        let mut visited = HashSet::new();
+        while let (Err(Error::NoSuchMethod { .. }), Some(address)) = (&found, parent) {
+            // a cycle of parents misses
+            if !visited.insert(address.to_owned()) {
+                break;
+            }
+            let (method, next) = self.read_address(&address, |class: &ClassObject| {
+                (
+                    class.get_method(name).ok().cloned(),
+                    class.parent().cloned(),
+                )
+            })?;
+            if let Some(method) = method {
+                return Ok(method);
+            }
+            parent = next;
+        }
+        found
+    }
+
+    pub fn push_ok(&mut self, index: usize) -> Result<()> {
+        let payload = self.address_of(index)?;
+        self.push_new(Object::new(ResultObject::Ok(payload)))
+            .map(drop)
+    }
+
+    pub fn push_err(&mut self, index: usize) -> Result<()> {
+        let payload = self.address_of(index)?;
+        self.push_new(Object::new(ResultObject::Err(payload)))
+            .map(drop)
+    }
+
+    pub fn push_nil(&mut self) -> Result<()> {
+        let nil = self.nil.to_owned();
+        self.with_current_frame_mut(|frame| frame.push_address(nil));
+        Ok(())
+    }
+
+    pub fn is_nil(&self, index: usize) -> Result<bool> {
+        Ok(match self.get_entry(index)? {
+            StackEntry::Heap(address) => address == self.nil,
+            StackEntry::Temp(_) | StackEntry::Small(_) => false,
+        })
+    }
+
+    // nil is the only falsy object, there are no booleans yet
+    pub fn is_truthy(&self, index: usize) -> Result<bool> {
+        Ok(!self.is_nil(index)?)
+    }
+
+    pub fn is_ok(&self, index: usize) -> Result<bool> {
+        self.read_object(index, |result: &ResultObject| result.is_ok())
+    }
+
+    // pushes the payload of an ok result
+    pub fn unwrap_result(&mut self, index: usize) -> Result<()> {
+        let (is_ok, payload) = self.read_object(index, |result: &ResultObject| {
+            (result.is_ok(), result.payload().to_owned())
+        })?;
+        if !is_ok {
+            let debug = format!("{:?}", self.address_handle(&payload)?.object());
+            return Err(Error::UnwrappedErr(debug));
+        }
+        self.with_current_frame_mut(|frame| frame.push_address(payload));
+        Ok(())
+    }
+
+    // for an ok result, calls `method` with the payload as the only argument and `body`
+    // running it, which returns the index of the mapped value on callee's stack; pushes an
+    // ok result of that value, or the err result itself without calling anything
+    pub fn result_map<F>(&mut self, result: usize, method: usize, body: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<usize>,
+    {
+        let (is_ok, payload) = self.read_object(result, |result: &ResultObject| {
+            (result.is_ok(), result.payload().to_owned())
+        })?;
+        if !is_ok {
+            let result = self.address_of(result)?;
+            self.with_current_frame_mut(|frame| frame.push_address(result));
+            return Ok(());
+        }
+        let stack_len = self.stack_len();
+        self.with_current_frame_mut(|frame| frame.push_address(payload));
+        let mapped = self.call_scoped(
+            method + 1,
+            &[1],
+            |runtime| {
+                let index = body(runtime)?;
+                runtime.back(&[index])
+            },
+            |_| Ok(()),
+        );
+        let mapped = mapped.and_then(|()| self.push_ok(1));
+        // leaves the result alone on top, in place of the payload and the mapped value
+        self.with_current_frame_mut(|frame| {
+            if mapped.is_ok() {
+                frame.stack.swap(stack_len, stack_len + 2);
+                frame.truncate(stack_len + 1);
+            } else {
+                frame.truncate(stack_len);
+            }
+        });
+        mapped
+    }
+
+    fn wait_object(&self, address: &Address) -> Result<Object> {
+        loop {
+            let take = self.memory.lock().take(address)?;
+            match take {
+                Take::Free(object) => {
+                    if object.downcast_ref::<AbandonedObject>().is_ok() {
+                        self.memory.lock().fill(address, object)?;
+                        return Err(Error::Abandoned);
+                    }
+                    return Ok(object);
+                }
+                Take::Busy(parker) => {
+                    self.check_deadlock(address)?;
+                    self.scheduler.yield_point("park");
+                    self.cancellation.park(parker)?;
+                }
+            }
+        }
+    }
+
+    fn roots(&self) -> Vec<Address> {
+        let mut roots = self.frame_stack.to_owned();
+        roots.extend(self.frame_pool.iter().cloned());
+        roots.extend(self.pending_frame.iter().cloned());
+        roots.extend(self.pins.lock().keys().cloned());
+        roots.extend(self.globals.values().cloned());
+        roots.push(self.nil.to_owned());
+        roots
+    }
+
+    // the root is otherwise refreshed right before allocating, which would leave a new frame
+    // or global unrooted for a collection by the embedder or by another runtime
+    fn refresh_roots(&self) {
+        let mut memory = self.memory.lock();
+        refresh_root(&mut memory, &self.root, self.roots()).unwrap();
+    }
+
+    // keeps the object alive until the pin drops, even when it is no longer reachable
+    pub fn pin(&mut self, index: usize) -> Result<Pin> {
+        let address = self.address_of(index)?;
+        let object = self.wait_object(&address)?;
+        let pointer = object.as_any() as *const dyn Any;
+        let type_id = object.as_any().type_id();
+        let type_name = object.type_name();
+        let mut memory = self.memory.lock();
+        memory.fill(&address, object)?;
+        *self.pins.lock().entry(address.to_owned()).or_insert(0) += 1;
+        refresh_root(&mut memory, &self.root, self.roots())?;
+        Ok(Pin {
+            pins: self.pins.to_owned(),
+            address,
+            pointer,
+            type_id,
+            type_name,
+        })
+    }
+
+    fn allocate(&mut self, object: Object) -> Result<Address> {
+        self.allocate_limited(object, self.allocation_limit)
+    }
+
+    // for frames, which may use the slots above the allocation limit
+    fn allocate_internal(&mut self, object: Object) -> Result<Address> {
+        self.allocate_limited(object, None)
+            .map_err(|err| match err {
+                Error::OutOfMemory { .. } => Error::InternalOutOfMemory,
+                err => err,
+            })
+    }
+
+    fn allocate_limited(&mut self, object: Object, limit: Option<usize>) -> Result<Address> {
+        self.with_collector(|memory| allocate_in(memory, object, limit))?
+    }
+
+    // fails before anything is allocated unless the temporaries among `indices` can be
+    // promoted and `frames` frames allocated afterwards, collecting once if needed; without
+    // a known capacity only the allocation limit can be checked
+    fn reserve(&mut self, indices: &[usize], frames: usize) -> Result<()> {
+        let mut temps = HashSet::new();
+        for &index in indices {
+            if let StackEntry::Temp(_) | StackEntry::Small(_) = self.get_entry(index)? {
+                temps.insert(index);
+            }
+        }
+        let objects = temps.len();
+        let frames = frames.saturating_sub(self.frame_pool.len());
+        let limit = self.allocation_limit;
+        let capacity = self.collector_capacity;
+        self.with_collector(|memory| {
+            let check = |memory: &RawCollector<Slot<Object>>| {
+                let live = memory.alive_count();
+                match (limit, capacity) {
+                    (Some(limit), _) if objects > 0 && live + objects > limit => {
+                        Err(Error::OutOfMemory {
+                            live,
+                            capacity: limit,
+                        })
+                    }
+                    (_, Some(capacity)) if live + objects + frames > capacity => {
+                        Err(Error::OutOfMemory { live, capacity })
+                    }
+                    _ => Ok(()),
+                }
+            };
+            if check(memory).is_err() {
+                memory.collect()?;
+            }
+            check(memory)
+        })?
+    }
+
+    // for anything that may collect, with the root refreshed first
+    fn with_collector<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut RawCollector<Slot<Object>>) -> R,
+    {
+        let mut memory = self.memory.lock();
+        refresh_root(&mut memory, &self.root, self.roots())?;
+        let frame_object = match self.frame_cache.take() {
+            Some(frame_object) => frame_object,
+            None => return Ok(f(&mut memory)),
+        };
+        // the collector only knows the keep list cached when the frame was taken,
+        // so put the up-to-date frame back meanwhile
+        let frame = self.frame_stack.last().unwrap();
+        memory.fill(frame, Object::new(frame_object))?;
+        let result = f(&mut memory);
+        match memory.take(frame)? {
+            Take::Free(frame_object) => self.frame_cache = Some(frame_object.downcast()?),
+            Take::Busy(_) => unreachable!(),
+        }
+        Ok(result)
+    }
+
+    pub fn memory_stats(&self) -> CollectorStats {
+        CollectorStats {
+            allocated: self.memory.lock().alive_count(),
+            capacity: self.collector_capacity,
+        }
+    }
+
+    // collects right away instead of waiting for the collector to fill up; the collector
+    // is only locked inside, so this works between any two operations, including inside
+    // `hold_frame`
+    pub fn collect_now(&mut self) -> Result<CollectReport> {
+        self.with_collector(|memory| {
+            let before = memory.alive_count();
+            memory.collect()?;
+            Ok(CollectReport {
+                reclaimed: before - memory.alive_count(),
+                alive: memory.alive_count(),
+            })
+        })?
+    }
+
+    fn load_frame(&mut self) {
+        if self.frame_hold == 0 {
+            return;
+        }
+        let frame = self.frame_stack.last().unwrap();
+        let object = self.wait_object(frame).unwrap();
+        self.hold(frame, &object);
+        self.frame_cache = Some(object.downcast().unwrap());
+    }
+
+    fn store_frame(&mut self) {
+        if let Some(frame_object) = self.frame_cache.take() {
+            let frame = self.frame_stack.last().unwrap();
+            self.release(frame);
+            self.memory
+                .lock()
+                .fill(frame, Object::new(frame_object))
+                .unwrap();
+        }
+    }
+
+    fn allocate_frame(&mut self, mut frame_object: Frame) -> Result<Address> {
+        frame_object.argument_count = frame_object.stack_len();
+        let frame = match self.frame_pool.pop() {
+            Some(frame) => frame,
+            None => return self.allocate_internal(Object::new(frame_object)),
+        };
+        self.wait_object(&frame)?;
+        self.memory.lock().fill(&frame, Object::new(frame_object))?;
+        Ok(frame)
+    }
+
+    // leaves the frame's slot taken, as nothing should access it until it is recycled
+    fn take_current_frame(&mut self) -> Frame {
+        match self.frame_cache.take() {
+            Some(frame_object) => frame_object,
+            None => {
+                let frame = self.frame_stack.last().unwrap();
+                self.wait_object(frame).unwrap().downcast().unwrap()
+            }
+        }
+    }
+
+    // frames which don't fit in the pool stay taken until the collector frees them
+    fn recycle_frame(&mut self, frame: Address) {
+        if self.frame_pool.len() == self.frame_pool_size {
+            return;
+        }
+        // clear the stale stack so that it doesn't keep garbage alive
+        let idle = Frame::idle(self.frame_stack[0].to_owned());
+        self.memory.lock().fill(&frame, Object::new(idle)).unwrap();
+        self.frame_pool.push(frame);
+    }
+
+    // keeps the current frame taken for the duration of `f`; the collector only knows the
+    // keep list left in the slot when the frame was taken, and while this runtime puts the
+    // up-to-date frame back around its own collections, another runtime sharing the
+    // collector may not collect inside `f`, or it would free what the frame pushed since
+    pub fn hold_frame<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        self.frame_hold += 1;
+        if self.frame_hold == 1 {
+            self.load_frame();
+        }
+        let result = f(self);
+        self.frame_hold -= 1;
+        if self.frame_hold == 0 {
+            self.store_frame();
+        }
+        result
+    }
+
+    fn with_current_frame_ref<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Frame) -> R,
+    {
+        if let Some(frame_object) = &self.frame_cache {
+            return f(frame_object);
+        }
+        self.with_stored_frame_ref(self.frame_stack.last().unwrap(), f)
+    }
+
+    fn with_stored_frame_ref<F, R>(&self, frame: &Address, f: F) -> R
+    where
+        F: FnOnce(&Frame) -> R,
+    {
+        let handle = self.address_handle(frame).unwrap();
+        f(handle.object().downcast_ref().unwrap())
+    }
+
+    fn with_current_frame_mut<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Frame) -> R,
+    {
+        self.hold_frame(|runtime| f(runtime.frame_cache.as_mut().unwrap()))
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn stack_len(&self) -> usize {
+        self.with_current_frame_ref(|frame| frame.stack_len())
+    }
+
+    pub fn load(&mut self, source: Source) -> Result<Address> {
+        match source {
+            Source::Local(index) => self.address_of(index),
+            Source::Arg(n) => {
+                let index = self.with_current_frame_ref(|frame| {
+                    if n == 0
+                        || n > frame.argument_count
+                        || frame.argument_count > frame.stack_len()
+                    {
+                        return Err(Error::ExhaustedFrame);
+                    }
+                    Ok(frame.stack_len() - frame.argument_count + n)
+                })?;
+                self.address_of(index)
+            }
+            Source::Parent(index) => {
+                let parent = self.with_current_frame_ref(|frame| frame.parent.to_owned());
+                self.stored_address_of(&parent.ok_or(Error::NoParentFrame)?, index)
+            }
+            Source::Context => Ok(self.context_address()),
+        }
+    }
+
+    // same as `address_of` but for a frame other than the current one
+    fn stored_address_of(&mut self, frame: &Address, index: usize) -> Result<Address> {
+        let entry = self
+            .with_stored_frame_ref(frame, |frame| frame.get_entry(index).map(ToOwned::to_owned))?;
+        if let StackEntry::Heap(address) = entry {
+            return Ok(address);
+        }
+        let address = self.allocate(Object::new(Placeholder))?;
+        let mut frame_object: Frame = self.wait_object(frame)?.downcast()?;
+        let object = frame_object.promote(index, address.to_owned());
+        let mut memory = self.memory.lock();
+        memory.fill(frame, Object::new(frame_object))?;
+        memory.replace(&address, Slot::new(object))?;
+        Ok(address)
+    }
+
+    pub fn context_address(&self) -> Address {
+        self.with_current_frame_ref(|frame| frame.context.to_owned())
+    }
+
+    // innermost frame first, the root frame's depth is 0
+    pub fn backtrace(&self) -> Vec<FrameInfo> {
+        let depth = self.frame_stack.len() - 1;
+        let mut backtrace = vec![self.with_current_frame_ref(|frame| FrameInfo::new(frame, depth))];
+        for (depth, frame) in self.frame_stack[..depth].iter().enumerate().rev() {
+            backtrace.push(self.with_stored_frame_ref(frame, |frame| FrameInfo::new(frame, depth)));
+        }
+        backtrace
+    }
+
+    // never waits for objects, which are reported busy instead
+    pub fn view(&self) -> RuntimeView {
+        let depth = self.frame_stack.len() - 1;
+        let current = &self.frame_stack[depth];
+        let mut frames = vec![self.with_current_frame_ref(|frame| self.frame_view(current, frame))];
+        for address in self.frame_stack[..depth].iter().rev() {
+            frames
+                .push(self.with_stored_frame_ref(address, |frame| self.frame_view(address, frame)));
+        }
+        RuntimeView {
+            frames,
+            alive_count: self.memory.lock().alive_count(),
+        }
+    }
+
+    fn frame_view(&self, address: &Address, frame: &Frame) -> FrameView {
+        let stack = frame
+            .stack
+            .iter()
+            .rev()
+            .map(|entry| match entry {
+                StackEntry::Temp(temp) => frame.temps[*temp].as_ref().unwrap().type_name(),
+                StackEntry::Small(_) => std::any::type_name::<IntObject>(),
+                StackEntry::Heap(address) => {
+                    let mut memory = self.memory.lock();
+                    match memory.take(address) {
+                        Ok(Take::Free(object)) => {
+                            let type_name = object.type_name();
+                            memory.fill(address, object).unwrap();
+                            type_name
+                        }
+                        _ => BUSY,
+                    }
+                }
+            })
+            .collect();
+        FrameView {
+            address: address.to_owned(),
+            stack,
+        }
+    }
+
+    // the returned handle is kept up to date at every call and return
+    pub fn view_channel(&mut self) -> ViewHandle {
+        let handle = self
+            .view_handle
+            .get_or_insert_with(ViewHandle::default)
+            .to_owned();
+        self.publish_view();
+        handle
+    }
+
+    fn profile_enter(&mut self) {
+        if let Some(mut profiler) = self.profiler.take() {
+            profiler.enter(self.context_address());
+            self.profiler = Some(profiler);
+        }
+    }
+
+    fn profile_exit(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+    }
+
+    // empty unless profiling was enabled when booting
+    pub fn profile_report(&self) -> Vec<MethodProfile> {
+        self.profiler
+            .as_ref()
+            .map(Profiler::report)
+            .unwrap_or_default()
+    }
+
+    pub fn reset_profile(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset();
+        }
+    }
+
+    fn publish_view(&self) {
+        if let Some(handle) = &self.view_handle {
+            handle.publish(self.view());
+        }
+    }
+
+    // makes the context reachable by index, e.g. to pass it on as context of a `call`
+    pub fn push_context(&mut self) {
+        let context = self.context_address();
+        self.with_current_frame_mut(|frame| frame.push_address(context));
+    }
+
+    // only the current frame changes, the next call of the same method starts from
+    // whatever context its caller passes again
+    pub fn rebind_context(&mut self, index: usize) -> Result<Address> {
+        let context = self.address_of(index)?;
+        Ok(self.with_current_frame_mut(|frame| std::mem::replace(&mut frame.context, context)))
+    }
+
+    fn make_frame(
+        &mut self,
+        context: usize,
+        arguments: &[usize],
+        parent: Option<Address>,
+    ) -> Result<Frame> {
+        let mut indices = vec![context];
+        indices.extend_from_slice(arguments);
+        self.reserve(&indices, 1)?;
+        let mut frame = Frame::new(self.address_of(context)?, parent);
+        for arg in arguments.iter().rev() {
+            frame.push_address(self.address_of(*arg)?);
+        }
+        Ok(frame)
+    }
+
+    fn push_frame(&mut self, frame_object: Frame) -> Result<()> {
+        self.cancellation.check()?;
+        if let Some(depth) = self.max_depth {
+            if self.frame_stack.len() >= depth {
+                return Err(Error::StackOverflow);
+            }
+        }
+        self.scheduler.yield_point("call");
+        let frame = self.allocate_frame(frame_object)?;
+        self.store_frame();
+        self.frame_stack.push(frame);
+        self.refresh_roots();
+        self.load_frame();
+        self.profile_enter();
+        self.publish_view();
+        Ok(())
+    }
+
+    pub fn call(&mut self, context: usize, arguments: &[usize]) -> Result<()> {
+        let caller_frame = self.frame_stack.last().unwrap().to_owned();
+        let callee_frame_object = self.make_frame(context, arguments, Some(caller_frame))?;
+        self.push_frame(callee_frame_object)
+    }
+
+    pub fn call_spread(&mut self, context: usize, list: usize) -> Result<()> {
+        let caller_frame = self.frame_stack.last().unwrap().to_owned();
+        let mut callee_frame_object = self.make_frame(context, &[], Some(caller_frame))?;
+        let items = self.read_object(list, |list: &ListObject| list.items().to_vec())?;
+        // same order as `call`: the first item ends up at index 1 of callee's stack
+        for item in items.into_iter().rev() {
+            callee_frame_object.push_address(item);
+        }
+        self.push_frame(callee_frame_object)
+    }
+
+    // enters the method `name` of the class at `class` with the receiver as its context,
+    // until the callee goes `back`; method bodies are run by the embedder, so the method's
+    // address is returned for it to dispatch on
+    pub fn call_method(
+        &mut self,
+        receiver: usize,
+        class: usize,
+        name: &str,
+        arguments: &[usize],
+    ) -> Result<Address> {
+        let method = self.get_method(class, name)?;
+        let method_name = self.method_name(class, name)?;
+        self.call(receiver, arguments)?;
+        self.with_current_frame_mut(|frame| frame.method_name = Some(method_name));
+        Ok(method)
+    }
+
+    fn method_name(&self, class: usize, name: &str) -> Result<String> {
+        self.read_object(class, |class: &ClassObject| {
+            format!("{}.{}", class.name(), name)
+        })
+    }
+
+    // calls the native method `name` of the class at `class` on the receiver, leaving the
+    // values it returns from index 1 on; returns how many there are. An error of the method
+    // comes with the backtrace from where it failed
+    pub fn invoke(
+        &mut self,
+        receiver: usize,
+        class: usize,
+        name: &str,
+        arguments: &[usize],
+    ) -> Result<usize> {
+        let method = self.get_method(class, name)?;
+        let body = self.read_address(&method, |method: &NativeMethod| method.0)?;
+        let method_name = self.method_name(class, name)?;
+        self.call_scoped(
+            receiver,
+            arguments,
+            |runtime| {
+                runtime.with_current_frame_mut(|frame| frame.method_name = Some(method_name));
+                let returned = body(runtime).and_then(|count| {
+                    runtime
+                        .back(&(1..=count).collect::<Vec<_>>())
+                        .map(|()| count)
+                });
+                returned.map_err(|err| runtime.attach_backtrace(err))
+            },
+            |_| Ok(()),
+        )
+    }
+
+    // keeps the innermost backtrace, from where the error was raised
+    fn attach_backtrace(&self, err: Error) -> Error {
+        match err {
+            Error::WithBacktrace { .. } => err,
+            err => Error::WithBacktrace {
+                source: Box::new(err),
+                frames: self.backtrace(),
+            },
+        }
+    }
+
+    // calls `body` with each item of the list, or each key of the dict, pushed onto the
+    // stack, discarding whatever it leaves there; neither must be changed meanwhile
+    pub fn for_each<F>(&mut self, list: usize, mut body: F) -> Result<()>
+    where
+        F: FnMut(&mut Self) -> Result<()>,
+    {
+        let list = self.address_of(list)?;
+        // a dict's keys are listed up front, the dict keeps them while its version holds
+        let (version, keys) = match self.read_address(&list, |list: &ListObject| list.version()) {
+            Ok(version) => (version, None),
+            Err(Error::TypeMismatch { .. }) => self.read_address(&list, |dict: &ObjectDict| {
+                (
+                    dict.version(),
+                    Some(dict.keys().cloned().collect::<Vec<_>>()),
+                )
+            })?,
+            Err(err) => return Err(err),
+        };
+        for position in 0.. {
+            let item = match &keys {
+                None => self.read_address(&list, |list: &ListObject| {
+                    if list.version() != version {
+                        return Err(Error::ConcurrentModification);
+                    }
+                    Ok(list.get(position).map(ToOwned::to_owned))
+                })??,
+                Some(keys) => self.read_address(&list, |dict: &ObjectDict| {
+                    if dict.version() != version {
+                        return Err(Error::ConcurrentModification);
+                    }
+                    Ok(keys.get(position).map(ToOwned::to_owned))
+                })??,
+            };
+            let item = match item {
+                Some(item) => item,
+                None => break,
+            };
+            let stack_len = self.stack_len();
+            self.with_current_frame_mut(|frame| frame.push_address(item));
+            let result = body(self);
+            self.with_current_frame_mut(|frame| frame.truncate(stack_len));
+            result?;
+        }
+        Ok(())
+    }
+
+    pub fn collect_args(&mut self) -> Result<()> {
+        let items = (1..=self.stack_len())
+            .map(|index| self.address_of(index))
+            .collect::<Result<_>>()?;
+        // allocate before clearing the stack, so the items stay reachable meanwhile
+        let list = self.allocate(Object::new(ListObject::new(items)))?;
+        self.with_current_frame_mut(|frame| {
+            frame.clear();
+            frame.push_address(list);
+            frame.argument_count = 1;
+        });
+        Ok(())
+    }
+
+    pub fn tail_call(&mut self, context: usize, arguments: &[usize]) -> Result<()> {
+        if self.frame_stack.len() == 1 {
+            return Err(Error::NoParentFrame);
+        }
+        // the generator would be left with the replaced frame, recycled meanwhile
+        if self.with_current_frame_ref(|frame| frame.generator) {
+            return Err(Error::TailCallInGenerator);
+        }
+        self.cancellation.check()?;
+        // everything which may fail comes before the deferred steps, so that a failed tail
+        // call leaves the caller as it was
+        let parent = self.with_current_frame_ref(|frame_object| frame_object.parent.to_owned());
+        let callee_frame_object = self.make_frame(context, arguments, parent)?;
+        let callee_frame = self.allocate_frame(callee_frame_object)?;
+        self.pending_frame = Some(callee_frame.to_owned());
+        self.refresh_roots();
+        let deferred_result = self.run_deferred();
+        self.pending_frame = None;
+        self.scheduler.yield_point("call");
+        self.take_current_frame();
+        self.profile_exit();
+        let replaced_frame = self.frame_stack.pop().unwrap();
+        self.recycle_frame(replaced_frame);
+        self.frame_stack.push(callee_frame);
+        self.refresh_roots();
+        self.load_frame();
+        self.profile_enter();
+        self.publish_view();
+        deferred_result
+    }
+
+    // same order as `call`: the first returned object ends up at index 1 of caller's stack
+    pub fn back(&mut self, returned: &[usize]) -> Result<()> {
+        if self.frame_stack.len() == 1 {
+            return Err(Error::NoParentFrame);
+        }
+        self.cancellation.check()?;
+        self.reserve(returned, 0)?;
+        // returned temporaries are promoted, the others are dropped along with the frame
+        let returned = returned
+            .iter()
+            .map(|ret| self.address_of(*ret))
+            .collect::<Result<Vec<_>>>()?;
+        self.scheduler.yield_point("back");
+        let deferred_result = self.pop_frame();
+        self.with_current_frame_mut(|caller_frame_object| {
+            for addr in returned.into_iter().rev() {
+                caller_frame_object.push_address(addr);
+            }
+        });
+        deferred_result
+    }
+
+    // returns the whole stack, which keeps its order on caller's stack
+    pub fn back_all(&mut self) -> Result<()> {
+        let returned: Vec<_> = (1..=self.stack_len()).collect();
+        self.back(&returned)
+    }
+
+    pub fn defer<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Runtime) -> Result<()> + 'static,
+    {
+        self.defer_keeping(Vec::new(), f)
+    }
+
+    // for a step capturing addresses which may be unreachable otherwise by the time it runs
+    pub fn defer_keeping<F>(&mut self, addresses: Vec<Address>, f: F)
+    where
+        F: FnOnce(&mut Runtime) -> Result<()> + 'static,
+    {
+        self.with_current_frame_mut(|frame| {
+            frame.deferred_keep.extend(addresses);
+            frame.deferred.push(Box::new(f));
+        });
+    }
+
+    // every deferred step runs even if an earlier one fails, and their stack effects are
+    // discarded
+    fn run_deferred(&mut self) -> Result<()> {
+        let deferred = self.with_current_frame_mut(|frame| std::mem::take(&mut frame.deferred));
+        let mut result = Ok(());
+        for f in deferred.into_iter().rev() {
+            let stack_len = self.stack_len();
+            result = chain_cleanup(result, f(self));
+            self.with_current_frame_mut(|frame| frame.truncate(stack_len));
+        }
+        self.with_current_frame_mut(|frame| frame.deferred_keep.clear());
+        result
+    }
+
+    fn pop_frame(&mut self) -> Result<()> {
+        let result = self.run_deferred();
+        self.take_current_frame();
+        self.profile_exit();
+        let callee_frame = self.frame_stack.pop().unwrap();
+        self.recycle_frame(callee_frame);
+        self.load_frame();
+        self.publish_view();
+        result
+    }
+
+    // pushes a generator, whose `step` runs once per `resume_generator` in a frame of its
+    // own; the frame's stack is kept between runs, but a native closure can't stop
+    // halfway, so `step` has to pick up from whatever state it left there (or captured)
+    pub fn call_generator<F>(&mut self, context: usize, arguments: &[usize], step: F) -> Result<()>
+    where
+        F: FnMut(&mut Runtime) -> Result<()> + 'static,
+    {
+        let mut frame_object = self.make_frame(context, arguments, None)?;
+        frame_object.argument_count = frame_object.stack_len();
+        frame_object.generator = true;
+        let frame = self.allocate(Object::new(frame_object))?;
+        self.push_temp(Object::new(GeneratorObject::new(frame, Box::new(step))))
+    }
+
+    // the generator finishes when `step` returns without yielding, returns through
+    // `back`, or fails
+    pub fn resume_generator(&mut self, index: usize) -> Result<GeneratorStep> {
+        self.cancellation.check()?;
+        let generator = self.address_of(index)?;
+        let (frame, mut step) = self.with_generator(&generator, GeneratorObject::start)?;
+        let stack_len = self.stack_len();
+        let depth = self.frame_stack.len() + 1;
+        self.scheduler.yield_point("call");
+        self.store_frame();
+        self.frame_stack.push(frame);
+        self.load_frame();
+        self.profile_enter();
+        self.publish_view();
+
+        self.generator_yields.push(None);
+        let mut result = step(self);
+        let yielded = self.generator_yields.pop().unwrap();
+        let returned = self.frame_stack.len() < depth;
+        if !returned {
+            while self.frame_stack.len() > depth {
+                result = chain_cleanup(result, self.pop_frame());
+            }
+            // the frame is left as it is for the next run
+            self.store_frame();
+            self.profile_exit();
+            self.frame_stack.pop();
+            self.load_frame();
+            self.publish_view();
+        }
+        let yielded = yielded.filter(|_| !returned && result.is_ok());
+        let paused = self.with_generator(&generator, |generator| {
+            match yielded {
+                Some(_) => generator.pause(step),
+                None => generator.finish(),
+            }
+            Ok(())
+        });
+        chain_cleanup(result, paused)?;
+        match yielded {
+            Some(value) => {
+                self.with_current_frame_mut(|frame| frame.push_address(value));
+                Ok(GeneratorStep::Yielded)
+            }
+            None => Ok(GeneratorStep::Done {
+                pushed: self.stack_len() - stack_len,
+            }),
+        }
+    }
+
+    fn with_generator<F, R>(&self, generator: &Address, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut GeneratorObject) -> Result<R>,
+    {
+        self.address_handle(generator)?
+            .object_mut()
+            .downcast_mut()
+            .and_then(f)
+    }
+
+    // only takes effect when `step` returns, the latest yield wins
+    pub fn yield_value(&mut self, index: usize) -> Result<()> {
+        if self.generator_yields.is_empty() {
+            return Err(Error::NotInGenerator);
+        }
+        let value = self.address_of(index)?;
+        *self.generator_yields.last_mut().unwrap() = Some(value);
+        Ok(())
+    }
+
+    // refused while any object is taken out, since then some method is in the middle of
+    // using it; the runtime is handed back along with the error
+    pub fn suspend(self) -> std::result::Result<SuspendedRuntime, (Box<Runtime>, Error)> {
+        if !self.taken.is_empty() {
+            return Err((Box::new(self), Error::SuspendWhileTaken));
+        }
+        Ok(SuspendedRuntime { runtime: self })
+    }
+
+    // continues with the same frames, so `back` returns to the same callers as before
+    pub fn resume(suspended: SuspendedRuntime) -> Self {
+        let runtime = suspended.runtime;
+        runtime.publish_view();
+        runtime
+    }
+
+    // runs `body` in a new frame and then `exit` in the same frame even if `body` fails,
+    // with any frame left over by either of them dropped; the caller's stack ends up as
+    // after `call` and `back(&[])`
+    pub fn call_scoped<B, X, R>(
+        &mut self,
+        context: usize,
+        arguments: &[usize],
+        body: B,
+        exit: X,
+    ) -> Result<R>
+    where
+        B: FnOnce(&mut Self) -> Result<R>,
+        X: FnOnce(&mut Self) -> Result<()>,
+    {
+        let depth = self.frame_stack.len() + 1;
+        self.call(context, arguments)?;
+        let mut result = body(self);
+        while self.frame_stack.len() > depth {
+            result = chain_cleanup(result, self.pop_frame());
+        }
+        result = chain_cleanup(result, exit(self));
+        while self.frame_stack.len() >= depth {
+            result = chain_cleanup(result, self.pop_frame());
+        }
+        result
+    }
+
+    // runs `body` in a runtime with a memory of its own, which is dropped afterwards
+    // with all its garbage; `body` returns how many values on top of its stack are
+    // moved back to this stack. Only objects keeping nothing can be passed to or
+    // returned from the sandbox, so no address of one memory ends up in the other
+    pub fn eval_sandboxed<F>(
+        &mut self,
+        arguments: &[usize],
+        opts: SandboxOpts,
+        body: F,
+    ) -> Result<usize>
+    where
+        F: FnOnce(&mut Runtime) -> Result<usize>,
+    {
+        // the sandbox drops every object it sees, so the collector stays local to it
+        #[allow(clippy::arc_with_non_send_sync)]
+        let memory: Collector = Arc::new(Mutex::new(RawCollector::new(opts.capacity)));
+        let context = allocate_in(&mut memory.lock(), Object::new(Placeholder), None)?;
+        let mut builder = RuntimeBuilder::new(memory, context)
+            .collector_capacity(opts.capacity)
+            .with_scheduler(self.scheduler.to_owned())
+            .with_cancellation(self.cancellation.to_owned());
+        if let Some(depth) = opts.max_depth {
+            builder = builder.max_depth(depth);
+        }
+        let mut sandbox = builder.boot()?;
+
+        let mut shared = Vec::new();
+        let result = self
+            .move_arguments(&mut sandbox, arguments, opts.share_arguments, &mut shared)
+            .and_then(|()| body(&mut sandbox))
+            .and_then(|count| sandbox.pop_results(count, &shared));
+        // shared arguments go back even if the sandbox failed; one which the sandbox still
+        // holds is abandoned, so that nobody in this memory waits for it forever
+        let mut restored = Ok(());
+        for (address, sandbox_address) in shared {
+            let take = sandbox.memory.lock().take(&sandbox_address);
+            let (object, taken) = match take {
+                Ok(Take::Free(object)) => (object, Ok(())),
+                Ok(Take::Busy(_)) => (Object::new(AbandonedObject), Err(Error::BusyObject)),
+                Err(err) => (Object::new(AbandonedObject), Err(err.into())),
+            };
+            let filled = self
+                .memory
+                .lock()
+                .fill(&address, object)
+                .map_err(Into::into);
+            restored = restored.and(taken).and(filled);
+        }
+        let results = chain_cleanup(result, restored)?;
+        let count = results.len();
+        for object in results {
+            self.push_new(object)?;
+        }
+        Ok(count)
+    }
+
+    // every slot is reserved before anything moves, so that a full sandbox fails before
+    // taking any argument out of this memory; an argument shared twice is moved once, and
+    // both of its entries in the sandbox refer to the same slot
+    fn move_arguments(
+        &mut self,
+        sandbox: &mut Runtime,
+        arguments: &[usize],
+        share: bool,
+        shared: &mut Vec<(Address, Address)>,
+    ) -> Result<()> {
+        let mut moves: Vec<(Address, Address)> = Vec::with_capacity(arguments.len());
+        for index in arguments {
+            let address = self.address_of(*index)?;
+            let moved = moves
+                .iter()
+                .find(|(moved, _)| share && *moved == address)
+                .map(|(_, sandbox_address)| sandbox_address.to_owned());
+            match moved {
+                Some(sandbox_address) => sandbox.push_address(sandbox_address)?,
+                None => {
+                    let sandbox_address = sandbox.push_new(Object::new(Placeholder))?;
+                    moves.push((address, sandbox_address));
+                }
+            }
+        }
+        for (address, sandbox_address) in moves {
+            let object = self.wait_object(&address)?;
+            let object = if share {
+                if let Err(err) = check_transferable(&object) {
+                    self.memory.lock().fill(&address, object)?;
+                    return Err(err);
+                }
+                shared.push((address, sandbox_address.to_owned()));
+                object
+            } else {
+                let copy = object.try_clone();
+                self.memory.lock().fill(&address, object)?;
+                let copy = copy.ok_or(Error::NotTransferable)?;
+                check_transferable(&copy)?;
+                copy
+            };
+            sandbox
+                .memory
+                .lock()
+                .replace(&sandbox_address, Slot::new(object))?;
+        }
+        Ok(())
+    }
+
+    // pops the top `count` objects, in stack order; a shared argument can't be returned,
+    // as it goes back to where it came from, and an object returned twice is cloned
+    fn pop_results(&mut self, count: usize, shared: &[(Address, Address)]) -> Result<Vec<Object>> {
+        if count > self.stack_len() {
+            return Err(Error::IndexOutOfRange);
+        }
+        let mut addresses = Vec::with_capacity(count);
+        for index in 1..=count {
+            addresses.push(match self.get_entry(index)? {
+                StackEntry::Heap(address) => Some(address),
+                StackEntry::Temp(_) | StackEntry::Small(_) => None,
+            });
+        }
+        let is_shared = |address: &Address| shared.iter().any(|(_, shared)| shared == address);
+        if addresses.iter().flatten().any(is_shared) {
+            return Err(Error::NotTransferable);
+        }
+        let mut results: Vec<Object> = Vec::with_capacity(count);
+        for (position, address) in addresses.iter().enumerate() {
+            let first = address.as_ref().and_then(|address| {
+                addresses[..position]
+                    .iter()
+                    .position(|popped| popped.as_ref() == Some(address))
+            });
+            let object = match first {
+                Some(first) => {
+                    self.pop()?;
+                    results[first].try_clone().ok_or(Error::NotTransferable)?
+                }
+                None => self.pop_object()?,
+            };
+            check_transferable(&object)?;
+            results.push(object);
+        }
+        results.reverse();
+        Ok(results)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SandboxOpts {
+    // slots of the sandbox memory, including its root, its frames and the arguments
+    pub capacity: usize,
+    pub max_depth: Option<usize>,
+    // moves the arguments into the sandbox for its run instead of passing clones
+    pub share_arguments: bool,
+}
+
+fn check_transferable(object: &Object) -> Result<()> {
+    let mut keeps_nothing = true;
+    object.with_keep(|keep_list| keeps_nothing = keep_list.is_empty());
+    if keeps_nothing {
+        Ok(())
+    } else {
+        Err(Error::NotTransferable)
+    }
+}
+
+// `Address` has no order of its own, but hashes as the bare number it wraps, which this
+// hasher keeps
+#[derive(Default)]
+struct AddressKey(usize);
+
+impl Hasher for AddressKey {
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!("an address hashes as a usize")
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.0 = value;
+    }
+}
+
+fn address_key(address: &Address) -> usize {
+    let mut key = AddressKey::default();
+    address.hash(&mut key);
+    key.0
+}
+
+fn chain_cleanup<R>(result: Result<R>, cleanup: Result<()>) -> Result<R> {
+    match (result, cleanup) {
+        (result, Ok(())) => result,
+        (Ok(_), Err(err)) => Err(err),
+        (Err(err), Err(cleanup)) => Err(Error::Cleanup {
+            error: Box::new(err),
+            cleanup: Box::new(cleanup),
+        }),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::core::object::Finalize;
+    use crate::core::sched::VirtualTimeScheduler;
+    use crate::objects::native::NativeBody;
+
+    // a context which keeps nothing
+    pub(crate) struct Unit;
+
+    impl Keep for Unit {
+        fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+    }
+
+    pub(crate) fn boot() -> Runtime {
+        boot_with().0
+    }
+
+    pub(crate) fn boot_with() -> (Runtime, Collector) {
+        let (collector, context) = memory(1 << 16);
+        let runtime = RuntimeBuilder::new(collector.to_owned(), context)
+            .boot()
+            .unwrap();
+        (runtime, collector)
+    }
+
+    // a collector with a `Unit` context allocated in it
+    pub(crate) fn memory(capacity: usize) -> (Collector, Address) {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let collector: Collector = Arc::new(Mutex::new(RawCollector::new(capacity)));
+        let context = collector
+            .lock()
+            .allocate(Slot::new(Object::new(Unit)))
+            .unwrap();
+        (collector, context)
+    }
+
+    #[test]
+    fn nested_hold_frame() {
+        let mut runtime = boot();
+        runtime.hold_frame(|runtime| {
+            runtime.push_new(Object::new(Unit)).unwrap();
+            runtime.hold_frame(|runtime| {
+                assert_eq!(runtime.stack_len(), 1);
+                runtime.push_new(Object::new(Unit)).unwrap();
+            });
+            assert_eq!(runtime.stack_len(), 2);
+            runtime.call(1, &[2]).unwrap();
+            assert_eq!(runtime.stack_len(), 1);
+            runtime.push_new(Object::new(Unit)).unwrap();
+            runtime.back(&[1, 2]).unwrap();
+            assert_eq!(runtime.stack_len(), 4);
+        });
+        assert_eq!(runtime.stack_len(), 4);
+        runtime.call(1, &[1]).unwrap();
+        runtime.hold_frame(|runtime| runtime.back(&[1]).unwrap());
+        assert_eq!(runtime.stack_len(), 5);
+        let object = runtime.take(1).unwrap();
+        runtime.fill(1, object).unwrap();
+        runtime.pop().unwrap();
+        assert_eq!(runtime.stack_len(), 4);
+    }
+
+    #[test]
+    fn hold_frame_across_own_collection() {
+        let (mut runtime, collector) = boot_with();
+        runtime.hold_frame(|runtime| {
+            runtime.push_int(7).unwrap();
+            runtime.collect_now().unwrap();
+            assert_eq!(runtime.get_int(1).unwrap(), 7);
+        });
+        collector.lock().collect().unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 7);
+    }
+
+    #[test]
+    fn tail_call_reuses_depth() {
+        let (collector, context) = memory(1 << 16);
+        // far fewer than the tail calls below, which would overflow as nested calls
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .max_depth(16)
+            .boot()
+            .unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        assert!(runtime.tail_call(1, &[]).is_err());
+        runtime.call(1, &[1]).unwrap();
+        for _ in 0..10000 {
+            runtime.tail_call(1, &[1, 1]).unwrap();
+            runtime.pop().unwrap();
+        }
+        runtime.hold_frame(|runtime| {
+            for _ in 0..100 {
+                runtime.tail_call(1, &[1]).unwrap();
+            }
+        });
+        assert_eq!(runtime.backtrace().len(), 2);
+        runtime.back(&[1]).unwrap();
+        assert_eq!(runtime.stack_len(), 2);
+        assert!(runtime.back(&[1]).is_err());
+    }
+
+    #[test]
+    fn dispatches_methods_by_name() {
+        let (mut runtime, collector) = boot_with();
+        let mut class = ClassObject::new("Thing");
+        let mut methods = Vec::new();
+        for name in &["get", "set"] {
+            let method = collector
+                .lock()
+                .allocate(Slot::new(Object::new(Unit)))
+                .unwrap();
+            class.set_method(name, method.to_owned());
+            methods.push(method);
+        }
+        runtime.push_int(42).unwrap();
+        runtime.push_new(Object::new(class)).unwrap();
+        assert_eq!(runtime.get_method(1, "get").unwrap(), methods[0]);
+        assert!(runtime.get_method(2, "get").is_err());
+
+        let method = runtime.call_method(2, 1, "set", &[2]).unwrap();
+        assert_eq!(method, methods[1]);
+        assert_eq!(
+            runtime.backtrace()[0].method_name.as_deref(),
+            Some("Thing.set")
+        );
+        assert_eq!(runtime.get_int(1).unwrap(), 42);
+        runtime.push_context();
+        assert_eq!(runtime.get_int(1).unwrap(), 42);
+        runtime.back(&[]).unwrap();
+        let method = runtime.call_method(2, 1, "get", &[]).unwrap();
+        assert_eq!(method, methods[0]);
+        runtime.push_context();
+        assert_eq!(runtime.get_int(1).unwrap(), 42);
+        runtime.back(&[]).unwrap();
+
+        let err = runtime.call_method(2, 1, "missing", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "no method missing in class Thing");
+        assert_eq!(runtime.backtrace().len(), 1);
+    }
+
+    #[test]
+    fn call_spread_pushes_list_items() {
+        struct Tag(usize);
+
+        impl Keep for Tag {
+            fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+        }
+
+        for &count in &[0, 1, 5] {
+            let mut runtime = boot();
+            for tag in 0..count {
+                runtime.push_new(Object::new(Tag(tag))).unwrap();
+            }
+            runtime.push_new(Object::new(Unit)).unwrap();
+            let arguments: Vec<_> = (2..=count + 1).collect();
+            runtime.call(1, &arguments).unwrap();
+            runtime.collect_args().unwrap();
+            assert_eq!(runtime.stack_len(), 1);
+            runtime.back(&[1]).unwrap();
+            runtime.push_new(Object::new(Unit)).unwrap();
+            runtime.call_spread(1, 2).unwrap();
+            assert_eq!(runtime.stack_len(), count);
+            for index in 1..=count {
+                let object = runtime.take(index).unwrap();
+                assert_eq!(object.downcast_ref::<Tag>().unwrap().0, count - index);
+                runtime.fill(index, object).unwrap();
+            }
+            runtime.back(&[]).unwrap();
+            assert!(runtime.call_spread(1, 1).is_err());
+        }
+    }
+
+    #[test]
+    fn pooled_frames_are_reused() {
+        let (collector, context) = memory(1 << 16);
+        let mut runtime = RuntimeBuilder::new(collector.to_owned(), context)
+            .frame_pool_size(4)
+            .boot()
+            .unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        let base = collector.lock().alive_count();
+        for _ in 0..10000 {
+            runtime.call(1, &[1]).unwrap();
+            runtime.call(1, &[1]).unwrap();
+            runtime.tail_call(1, &[1]).unwrap();
+            runtime.back(&[1]).unwrap();
+            runtime.back(&[1]).unwrap();
+            runtime.pop().unwrap();
+        }
+        assert_eq!(collector.lock().alive_count(), base);
+        runtime.hold_frame(|runtime| {
+            for _ in 0..6 {
+                runtime.call(1, &[]).ok();
+                runtime.push_new(Object::new(Unit)).unwrap();
+            }
+            for _ in 0..6 {
+                runtime.back(&[]).unwrap();
+            }
+        });
+        assert_eq!(collector.lock().alive_count(), base + 2 + 6);
+        assert_eq!(runtime.stack_len(), 1);
+    }
+
+    #[test]
+    fn pairs_takes_with_fills() {
+        let mut runtime = boot();
+        runtime.push_int(1).unwrap();
+        let old = runtime.replace(1, Object::new(IntObject(2))).unwrap();
+        assert_eq!(old.downcast_ref::<IntObject>().unwrap().0, 1);
+        let object = runtime.take(1).unwrap();
+        assert_eq!(object.downcast_ref::<IntObject>().unwrap().0, 2);
+        assert!(runtime.replace(1, Object::new(Unit)).is_err());
+        runtime.fill(1, object).unwrap();
+        let err = runtime.fill(1, Object::new(Unit)).unwrap_err();
+        assert_eq!(err.to_string(), "fill an object which was not taken");
+
+        runtime.call(1, &[1]).unwrap();
+        let object = runtime.take(1).unwrap();
+        runtime.back(&[1]).unwrap();
+        assert!(matches!(
+            runtime.fill(1, object),
+            Err(Error::FillWithoutTake)
+        ));
+    }
+
+    #[test]
+    fn typed_access() {
+        let mut runtime = boot();
+        runtime.push_int(41).unwrap();
+        runtime.push_str("hi").unwrap();
+        assert_eq!(runtime.get_str(1).unwrap(), "hi");
+        assert_eq!(runtime.get_int(2).unwrap(), 41);
+        assert!(runtime.get_int(1).is_err());
+        runtime.get_as_mut::<IntObject>(2).unwrap().0 += 1;
+        assert_eq!(runtime.get_as::<IntObject>(2).unwrap().0, 42);
+        assert!(runtime.get_as::<IntObject>(1).is_err());
+        assert_eq!(runtime.get_str(1).unwrap(), "hi");
+    }
+
+    #[test]
+    fn pushes_current_context() {
+        let mut runtime = boot();
+        let root_context = runtime.context_address();
+        runtime.push_int(1).unwrap();
+        runtime.call(1, &[]).unwrap();
+        assert_ne!(runtime.context_address(), root_context);
+        runtime.push_context();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        runtime.call(1, &[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        assert!(runtime.call(5, &[]).is_err());
+    }
+
+    #[test]
+    fn backtrace_lists_frames_innermost_first() {
+        let mut runtime = boot();
+        let root_context = runtime.context_address();
+        runtime.push_int(1).unwrap();
+        runtime.call(1, &[]).unwrap();
+        runtime.push_int(2).unwrap();
+        runtime.push_int(3).unwrap();
+        runtime.call(1, &[2]).unwrap();
+        let backtrace = runtime.backtrace();
+        assert_eq!(backtrace.len(), 3);
+        assert_eq!(backtrace[0].depth, 2);
+        assert_eq!(backtrace[0].stack_len, 1);
+        assert_eq!(backtrace[1].stack_len, 2);
+        assert_eq!(backtrace[2].context, root_context);
+        assert_eq!(backtrace[2].stack_len, 1);
+    }
+
+    #[test]
+    fn errors_from_methods_carry_the_backtrace() {
+        fn chain(runtime: &mut Runtime, name: &str) -> Result<usize> {
+            runtime.push_context();
+            runtime.push_global("Chain")?;
+            runtime.invoke(2, 1, name, &[])
+        }
+        fn outer(runtime: &mut Runtime) -> Result<usize> {
+            chain(runtime, "middle")
+        }
+        fn middle(runtime: &mut Runtime) -> Result<usize> {
+            chain(runtime, "inner")
+        }
+        fn inner(runtime: &mut Runtime) -> Result<usize> {
+            runtime.push_context();
+            runtime.get_str(1)?;
+            Ok(0)
+        }
+
+        let mut runtime = boot();
+        let mut class = ClassObject::new("Chain");
+        for &(name, body) in &[
+            ("outer", outer as NativeBody),
+            ("middle", middle),
+            ("inner", inner),
+        ] {
+            let method = runtime.push_new(Object::new(NativeMethod(body))).unwrap();
+            class.set_method(name, method);
+        }
+        runtime.push_new(Object::new(class)).unwrap();
+        runtime.set_global("Chain", 1).unwrap();
+        runtime.push_int(1).unwrap();
+        let stack_len = runtime.stack_len();
+        let err = runtime.invoke(1, 2, "outer", &[]).unwrap_err();
+        assert_eq!(runtime.stack_len(), stack_len);
+        assert_eq!(runtime.backtrace().len(), 1);
+        match &err {
+            Error::WithBacktrace { source, frames } => {
+                assert!(matches!(**source, Error::TypeMismatch { .. }));
+                let names: Vec<_> = frames.iter().map(ToString::to_string).collect();
+                assert_eq!(
+                    names,
+                    ["Chain.inner", "Chain.middle", "Chain.outer", "<root>"]
+                );
+            }
+            err => panic!("no backtrace in {:?}", err),
+        }
+        assert!(err
+            .to_string()
+            .ends_with("\n  at Chain.inner <- Chain.middle <- Chain.outer <- <root>"));
+        assert!(matches!(
+            err.without_backtrace(),
+            Error::TypeMismatch { .. }
+        ));
+
+        // frames entered without a method name still show up
+        runtime.call(1, &[]).unwrap();
+        let names: Vec<_> = runtime
+            .backtrace()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(names, ["<method>", "<root>"]);
+    }
+
+    #[test]
+    fn reports_out_of_memory_after_collecting() {
+        let (collector, context) = memory(32);
+        let mut runtime = RuntimeBuilder::new(collector.to_owned(), context)
+            .frame_pool_size(2)
+            .boot()
+            .unwrap();
+        for value in 0..200 {
+            runtime.push_int(value).unwrap();
+            runtime.call(1, &[1]).unwrap();
+            runtime.push_int(value).unwrap();
+            runtime.back(&[1]).unwrap();
+            assert_eq!(runtime.get_int(1).unwrap(), value);
+            runtime.pop().unwrap();
+            runtime.pop().unwrap();
+        }
+        let mut count = 0;
+        let err = loop {
+            match runtime.push_int(count) {
+                Ok(()) => count += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(
+            err,
+            Error::OutOfMemory {
+                live: 32,
+                capacity: 32
+            }
+        ));
+        for value in 0..count {
+            assert_eq!(runtime.get_int((count - value) as usize).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn frames_stay_rooted_across_collections() {
+        let (collector, context) = memory(1000);
+        let mut runtime = RuntimeBuilder::new(collector.to_owned(), context)
+            .frame_pool_size(2)
+            .boot()
+            .unwrap();
+        collector.lock().collect().unwrap();
+        runtime.push_int(1).unwrap();
+        collector.lock().collect().unwrap();
+        runtime.call(1, &[1]).unwrap();
+        runtime.push_int(2).unwrap();
+        collector.lock().collect().unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+        assert_eq!(runtime.get_int(2).unwrap(), 1);
+        runtime.back(&[1]).unwrap();
+        collector.lock().collect().unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+        assert_eq!(runtime.get_int(2).unwrap(), 1);
+    }
+
+    #[test]
+    fn keeps_embedder_root_and_frames() {
+        let (collector, context) = memory(64);
+        let embedder = collector
+            .lock()
+            .allocate(Slot::new(Object::new(IntObject(9))))
+            .unwrap();
+        collector.lock().set_root(embedder.to_owned());
+        let mut runtime = RuntimeBuilder::new(collector.to_owned(), context)
+            .boot()
+            .unwrap();
+        runtime.push_int(1).unwrap();
+        runtime.call(1, &[1]).unwrap();
+        collector.lock().collect().unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        runtime.tail_call(1, &[1]).unwrap();
+        collector.lock().collect().unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        runtime.set_global("one", 1).unwrap();
+        runtime.back(&[]).unwrap();
+        runtime.pop().unwrap();
+        collector.lock().collect().unwrap();
+        runtime.push_global("one").unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        let taken = collector.lock().take(&embedder).unwrap();
+        match taken {
+            Take::Free(object) => assert_eq!(object.downcast_ref::<IntObject>().unwrap().0, 9),
+            Take::Busy(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn failed_parse_result_leaves_stack_alone() {
+        let (collector, context) = memory(32);
+        let mut runtime = RuntimeBuilder::new(collector, context).boot().unwrap();
+        runtime.push_str("12").unwrap();
+        while runtime.push_int(0).is_ok() {}
+        runtime.pop().unwrap();
+        let stack_len = runtime.stack_len();
+        assert!(matches!(
+            runtime.parse_int(stack_len),
+            Err(Error::OutOfMemory { .. })
+        ));
+        assert_eq!(runtime.stack_len(), stack_len);
+    }
+
+    // pushes an ok result with the value of the key, or an err result with a message
+    fn lookup(runtime: &mut Runtime, key: &str) {
+        if key == "seven" {
+            runtime.push_int(7).unwrap();
+            runtime.push_ok(1).unwrap();
+        } else {
+            runtime.push_str("missing").unwrap();
+            runtime.push_err(1).unwrap();
+        }
+    }
+
+    #[test]
+    fn results_as_values() {
+        let mut runtime = boot();
+        for &key in &["seven", "eight"] {
+            runtime.push_new(Object::new(Unit)).unwrap();
+            runtime.call(1, &[]).unwrap();
+            lookup(&mut runtime, key);
+            runtime.back(&[1]).unwrap();
+            if runtime.is_ok(1).unwrap() {
+                runtime.unwrap_result(1).unwrap();
+                assert_eq!(runtime.get_int(1).unwrap(), 7);
+            } else {
+                assert!(matches!(
+                    runtime.unwrap_result(1),
+                    Err(Error::UnwrappedErr(debug)) if debug == "StringObject(\"missing\")"
+                ));
+            }
+        }
+        assert!(runtime.is_ok(3).is_err());
+    }
+
+    #[test]
+    fn result_map_skips_err() {
+        let mut runtime = boot();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        lookup(&mut runtime, "seven");
+        runtime
+            .result_map(1, 2, |runtime| {
+                let value = runtime.get_int(1)?;
+                runtime.push_int(value * 6)?;
+                Ok(1)
+            })
+            .unwrap();
+        assert_eq!(runtime.stack_len(), 4);
+        runtime.unwrap_result(1).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 42);
+
+        lookup(&mut runtime, "eight");
+        let stack_len = runtime.stack_len();
+        runtime.result_map(1, 5, |_| unreachable!()).unwrap();
+        assert_eq!(runtime.stack_len(), stack_len + 1);
+        assert!(!runtime.is_ok(1).unwrap());
+
+        lookup(&mut runtime, "seven");
+        let stack_len = runtime.stack_len();
+        let err = runtime.result_map(1, 1, |_| Err(Error::NotCallable));
+        assert!(matches!(err, Err(Error::NotCallable)));
+        assert_eq!(runtime.stack_len(), stack_len);
+        assert_eq!(runtime.backtrace().len(), 1);
+    }
+
+    fn boot_virtual() -> (Runtime, Arc<VirtualTimeScheduler>) {
+        let (collector, context) = memory(1 << 16);
+        let clock = Arc::new(VirtualTimeScheduler::new());
+        let runtime = RuntimeBuilder::new(collector, context)
+            .with_scheduler(clock.to_owned())
+            .boot()
+            .unwrap();
+        (runtime, clock)
+    }
+
+    #[test]
+    fn sleeps_in_virtual_time() {
+        let (mut runtime, clock) = boot_virtual();
+        runtime.now_millis().unwrap();
+        runtime.push_int(5000).unwrap();
+        runtime.sleep_millis(1).unwrap();
+        assert_eq!(clock.now(), Duration::from_millis(5000));
+        runtime.now_millis().unwrap();
+        assert_eq!(
+            runtime.get_int(1).unwrap() - runtime.get_int(3).unwrap(),
+            5000
+        );
+
+        let mut runtime = boot();
+        let mut last = 0;
+        for _ in 0..100 {
+            runtime.now_millis().unwrap();
+            let now = runtime.get_int(1).unwrap();
+            assert!(now >= last);
+            last = now;
+        }
+    }
+
+    #[test]
+    fn deadlines_and_stopwatches() {
+        let (mut runtime, clock) = boot_virtual();
+        runtime.push_int(100).unwrap();
+        runtime.deadline(1).unwrap();
+        runtime.start_stopwatch().unwrap();
+        assert!(!runtime.expired(2).unwrap());
+        clock.advance(Duration::from_millis(60));
+        runtime.push_int(40).unwrap();
+        runtime.sleep_millis(1).unwrap();
+        assert!(runtime.expired(3).unwrap());
+        runtime.elapsed_millis(2).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 100);
+        assert!(runtime.expired(1).is_err());
+    }
+
+    #[test]
+    fn cancellation_wakes_waiting_runtime() {
+        let token = CancellationToken::new();
+        let (collector, context) = memory(64);
+        let mut runtime = RuntimeBuilder::new(collector.to_owned(), context.to_owned())
+            .with_cancellation(token.to_owned())
+            .boot()
+            .unwrap();
+        runtime.push_context();
+        let _taken = collector.lock().take(&context).unwrap();
+        let canceller = token.to_owned();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        assert!(matches!(runtime.wait(1), Err(Error::Cancelled)));
+        handle.join().unwrap();
+        assert!(runtime.cancellation().is_cancelled());
+        assert!(matches!(runtime.call(1, &[]), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn mismatch_names_both_types() {
+        let mut runtime = boot();
+        runtime.push_str("x").unwrap();
+        let err = runtime.get_int(1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "type mismatch: expected shattuck::objects::int::IntObject, \
+             found shattuck::objects::string::StringObject"
+        );
+        let object = Object::new(Unit);
+        assert!(object.type_name().ends_with("Unit"));
+        let err = object.downcast::<IntObject>().unwrap_err();
+        assert!(err.to_string().contains("Unit"));
+    }
+
+    #[test]
+    fn temps_are_promoted_on_demand() {
+        let (mut runtime, collector) = boot_with();
+        runtime.push_int(0).unwrap();
+        let base = collector.lock().alive_count();
+        runtime.call(1, &[]).unwrap();
+        let after_call = collector.lock().alive_count();
+        for value in 0..1000 {
+            runtime.push_temp(Object::new(IntObject(value))).unwrap();
+        }
+        assert_eq!(collector.lock().alive_count(), after_call);
+        assert_eq!(runtime.get_int(1).unwrap(), 999);
+        assert_eq!(runtime.get_int(1000).unwrap(), 0);
+
+        runtime.push_temp(Object::new(IntObject(42))).unwrap();
+        runtime.call(1001, &[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 42);
+        runtime.back(&[]).unwrap();
+        runtime.pop().unwrap();
+        runtime.pop().unwrap();
+        runtime.back(&[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 998);
+        runtime.push_int(0).unwrap();
+        runtime.pop().unwrap();
+        collector.lock().collect().unwrap();
+        // only 998 is left promoted, 42 was popped with its frame
+        assert_eq!(collector.lock().alive_count(), base + 1);
+
+        runtime.push_temp(Object::new(IntObject(5))).unwrap();
+        runtime.collect_args().unwrap();
+        runtime.dump_graph(1).unwrap();
+        runtime.pop().unwrap();
+        runtime.push_temp(Object::new(IntObject(5))).unwrap();
+        let object = runtime.take(1).unwrap();
+        runtime.fill(1, object).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+    }
+
+    #[test]
+    fn call_scoped_always_exits() {
+        let mut runtime = boot();
+        runtime.push_int(0).unwrap();
+        let exits = Cell::new(0);
+        let value = runtime
+            .call_scoped(
+                1,
+                &[1],
+                |runtime| {
+                    runtime.push_int(5)?;
+                    runtime.call(1, &[])?;
+                    Ok(7)
+                },
+                |runtime| {
+                    assert_eq!(runtime.get_int(1)?, 5);
+                    exits.set(exits.get() + 1);
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert_eq!(value, 7);
+        assert_eq!(runtime.stack_len(), 1);
+        assert_eq!(runtime.backtrace().len(), 1);
+
+        let err = runtime.call_scoped(
+            1,
+            &[],
+            |_| -> Result<()> { Err(Error::InvalidUtf8) },
+            |_| {
+                exits.set(exits.get() + 1);
+                Ok(())
+            },
+        );
+        assert!(matches!(err, Err(Error::InvalidUtf8)));
+        let err = runtime
+            .call_scoped(
+                1,
+                &[],
+                |_| -> Result<()> { Err(Error::InvalidUtf8) },
+                |_| Err(Error::IndexOutOfRange),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid utf-8 (then cleanup failed: index out of range)"
+        );
+        assert_eq!(exits.get(), 2);
+        assert_eq!(runtime.stack_len(), 1);
+        assert_eq!(runtime.backtrace().len(), 1);
+    }
+
+    #[test]
+    fn deferred_steps_run_in_reverse() {
+        let mut runtime = boot();
+        runtime.push_int(0).unwrap();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        runtime.call(1, &[1]).unwrap();
+        for step in 0..3 {
+            let log = log.to_owned();
+            runtime.defer(move |runtime| {
+                runtime.push_int(9)?;
+                log.borrow_mut().push((step, runtime.get_int(2)?));
+                Ok(())
+            });
+        }
+        runtime.back(&[1]).unwrap();
+        assert_eq!(*log.borrow(), vec![(2, 0), (1, 0), (0, 0)]);
+        assert_eq!(runtime.stack_len(), 2);
+
+        log.borrow_mut().clear();
+        let body_log = log.to_owned();
+        let err = runtime
+            .call_scoped(
+                1,
+                &[],
+                |runtime| -> Result<()> {
+                    let log = body_log.to_owned();
+                    runtime.defer(move |_| {
+                        log.borrow_mut().push((1, 0));
+                        Ok(())
+                    });
+                    runtime.defer(|_| Err(Error::IndexOutOfRange));
+                    Err(Error::InvalidUtf8)
+                },
+                |_| Ok(()),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid utf-8 (then cleanup failed: index out of range)"
+        );
+        assert_eq!(*log.borrow(), vec![(1, 0)]);
+
+        runtime.call(1, &[]).unwrap();
+        runtime.defer(|_| Err(Error::IndexOutOfRange));
+        assert!(matches!(runtime.back(&[]), Err(Error::IndexOutOfRange)));
+        assert_eq!(runtime.backtrace().len(), 1);
+    }
+
+    #[test]
+    fn for_each_detects_modification() {
+        let mut runtime = boot();
+        for value in 1..=4 {
+            runtime.push_int(value).unwrap();
+        }
+        runtime.collect_args().unwrap();
+        let mut sum = 0;
+        runtime
+            .for_each(1, |runtime| {
+                sum += runtime.get_int(1)?;
+                runtime.push_int(0)
+            })
+            .unwrap();
+        assert_eq!(sum, 10);
+        assert_eq!(runtime.stack_len(), 1);
+
+        runtime
+            .push_new(Object::new(ListObject::new(Vec::new())))
+            .unwrap();
+        let mut visits = 0;
+        runtime
+            .for_each(1, |_| {
+                visits += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(visits, 0);
+        runtime.pop().unwrap();
+
+        let err = runtime.for_each(1, |runtime| {
+            runtime.push_int(5)?;
+            Err(Error::InvalidUtf8)
+        });
+        assert!(matches!(err, Err(Error::InvalidUtf8)));
+        assert_eq!(runtime.stack_len(), 1);
+        let err = runtime.for_each(1, |runtime| {
+            let context = runtime.context_address();
+            runtime.get_as_mut::<ListObject>(2)?.push(context);
+            Ok(())
+        });
+        assert!(matches!(err, Err(Error::ConcurrentModification)));
+        runtime.pop().unwrap();
+
+        runtime.push_new(Object::new(ObjectDict::new())).unwrap();
+        for value in 1..=3 {
+            runtime
+                .push_new(Object::with_vtable(IntObject(value)))
+                .unwrap();
+            runtime.dict_insert(2, 1, 1).unwrap();
+            runtime.pop().unwrap();
+        }
+        let mut sum = 0;
+        runtime
+            .for_each(1, |runtime| {
+                sum += runtime.get_int(1)?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(sum, 6);
+        let err = runtime.for_each(1, |runtime| {
+            runtime.push_new(Object::with_vtable(IntObject(4)))?;
+            runtime.dict_insert(3, 1, 1)
+        });
+        assert!(matches!(err, Err(Error::ConcurrentModification)));
+        // replacing the value of a key is a change as well
+        let err = runtime.for_each(1, |runtime| runtime.dict_insert(2, 1, 1));
+        assert!(matches!(err, Err(Error::ConcurrentModification)));
+        assert_eq!(runtime.stack_len(), 1);
+    }
+
+    #[test]
+    fn views_follow_calls() {
+        let mut runtime = boot();
+        runtime.push_int(1).unwrap();
+        runtime.push_str("s").unwrap();
+        let handle = runtime.view_channel();
+        assert_eq!(handle.get().depth(), 0);
+        runtime.call(2, &[1, 2]).unwrap();
+        runtime.push_temp(Object::new(Unit)).unwrap();
+        let _taken = runtime.take(2).unwrap();
+        let view = runtime.view();
+        assert_eq!(view.depth(), 1);
+        assert!(view.frames[0].stack[0].ends_with("Unit"));
+        assert_eq!(view.frames[0].stack[1], BUSY);
+        assert!(view.frames[0].stack[2].ends_with("IntObject"));
+        assert_eq!(view.frames[1].stack.len(), 2);
+        let remote = handle.to_owned();
+        let depth = std::thread::spawn(move || remote.get().depth());
+        assert_eq!(depth.join().unwrap(), 1);
+        runtime.call(1, &[]).unwrap();
+        assert_eq!(handle.get().depth(), 2);
+    }
+
+    #[derive(Clone, Default)]
+    struct Sink {
+        written: Arc<Mutex<Vec<u8>>>,
+        flushed: Arc<Mutex<usize>>,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.written.lock().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.flushed.lock() = self.written.lock().len();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn formats_and_prints() {
+        let sink = Sink::default();
+        let (collector, context) = memory(1 << 10);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .stdout(Box::new(sink.to_owned()))
+            .boot()
+            .unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime
+            .push_new(Object::with_vtable(FloatObject(0.5)))
+            .unwrap();
+        runtime.push_int(7).unwrap();
+        runtime.push_str("hi").unwrap();
+        runtime.push_str("{{{}}} {} {} {}!").unwrap();
+        runtime.format(1, &[2, 3, 4, 5]).unwrap();
+        assert_eq!(
+            runtime.get_str(1).unwrap(),
+            "{hi} 7 0.5 <shattuck::core::runtime::tests::Unit>!"
+        );
+        runtime.pop().unwrap();
+        assert!(matches!(
+            runtime.format(1, &[2]),
+            Err(Error::FormatArguments {
+                placeholders: 4,
+                arguments: 1
+            })
+        ));
+        let _taken = runtime.take(2).unwrap();
+        runtime.print(1, &[2, 3, 4, 5]).unwrap();
+        let printed = String::from_utf8(sink.written.lock().to_owned()).unwrap();
+        assert_eq!(
+            printed,
+            "{<busy>} 7 0.5 <shattuck::core::runtime::tests::Unit>!"
+        );
+        assert_eq!(*sink.flushed.lock(), printed.len());
+        runtime.push_str("{x").unwrap();
+        assert!(matches!(runtime.format(1, &[]), Err(Error::InvalidFormat)));
+    }
+
+    #[test]
+    fn pins_keep_objects_alive() {
+        let (mut runtime, collector) = boot_with();
+        runtime.push_int(11).unwrap();
+        let first = runtime.pin(1).unwrap();
+        let second = runtime.pin(1).unwrap();
+        runtime.pop().unwrap();
+        runtime.push_int(0).unwrap();
+        collector.lock().collect().unwrap();
+        let alive = collector.lock().alive_count();
+        let pointer = first.as_ptr::<IntObject>().unwrap();
+        assert_eq!(unsafe { (*pointer).0 }, 11);
+        assert!(first.as_ptr::<Unit>().is_err());
+        drop(first);
+        runtime.push_int(0).unwrap();
+        collector.lock().collect().unwrap();
+        // still pinned by the second pin
+        assert_eq!(collector.lock().alive_count(), alive + 1);
+        drop(second);
+        runtime.push_int(0).unwrap();
+        collector.lock().collect().unwrap();
+        assert_eq!(collector.lock().alive_count(), alive + 1);
+    }
+
+    #[test]
+    fn frames_may_exceed_allocation_limit() {
+        let (collector, context) = memory(16);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .allocation_limit(10)
+            .boot()
+            .unwrap();
+        let mut count = 0;
+        let err = loop {
+            match runtime.push_int(count) {
+                Ok(()) => count += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(
+            err,
+            Error::OutOfMemory {
+                live: 10,
+                capacity: 10
+            }
+        ));
+        runtime.call(1, &[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), count - 1);
+        let mut depth = 1;
+        let err = loop {
+            runtime.push_context();
+            match runtime.call(1, &[]) {
+                Ok(()) => depth += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(err, Error::InternalOutOfMemory));
+        assert_eq!(depth, 6);
+    }
+
+    #[test]
+    fn loads_from_sources() {
+        let mut runtime = boot();
+        assert!(matches!(
+            runtime.load(Source::Parent(1)),
+            Err(Error::NoParentFrame)
+        ));
+        for &value in &[10, 20, 30] {
+            runtime.push_int(value).unwrap();
+        }
+        let context = runtime.load(Source::Local(3)).unwrap();
+        let first = runtime.load(Source::Local(2)).unwrap();
+        let second = runtime.load(Source::Local(1)).unwrap();
+        runtime.call(3, &[2, 1]).unwrap();
+        assert_eq!(runtime.context_address(), context);
+        assert_eq!(runtime.load(Source::Context).unwrap(), context);
+        runtime.push_int(40).unwrap();
+        runtime.push_int(50).unwrap();
+        assert_eq!(runtime.load(Source::Arg(1)).unwrap(), first);
+        assert_eq!(runtime.load(Source::Arg(2)).unwrap(), second);
+        assert!(runtime.load(Source::Arg(3)).is_err());
+        assert_ne!(runtime.load(Source::Local(1)).unwrap(), second);
+        assert_eq!(runtime.load(Source::Parent(1)).unwrap(), second);
+        assert_eq!(runtime.load(Source::Parent(3)).unwrap(), context);
+
+        runtime.push_temp(Object::new(IntObject(7))).unwrap();
+        runtime.call(1, &[]).unwrap();
+        let promoted = runtime.load(Source::Parent(1)).unwrap();
+        assert_eq!(runtime.load(Source::Parent(1)).unwrap(), promoted);
+        runtime.back(&[]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 7);
+    }
+
+    #[test]
+    fn rebinds_current_context_only() {
+        let mut runtime = boot();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        let original = runtime.load(Source::Local(1)).unwrap();
+        runtime.call(1, &[]).unwrap();
+        assert!(runtime.rebind_context(0).is_err());
+        runtime.push_temp(Object::new(IntObject(5))).unwrap();
+        assert_eq!(runtime.rebind_context(1).unwrap(), original);
+        let rebound = runtime.context_address();
+        assert_eq!(runtime.load(Source::Local(1)).unwrap(), rebound);
+        runtime.pop().unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.pop().unwrap();
+        runtime.push_context();
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+        runtime.back(&[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+        runtime.call(2, &[]).unwrap();
+        assert_eq!(runtime.context_address(), original);
+    }
+
+    fn sandbox_opts(share_arguments: bool) -> SandboxOpts {
+        SandboxOpts {
+            capacity: 16,
+            max_depth: Some(4),
+            share_arguments,
+        }
+    }
+
+    #[test]
+    fn sandbox_garbage_stays_in_sandbox() {
+        let (mut runtime, collector) = boot_with();
+        runtime
+            .push_new(Object::with_vtable(AtomicIntObject::new(1)))
+            .unwrap();
+        runtime.push_int(2).unwrap();
+        collector.lock().collect().unwrap();
+        let before = collector.lock().alive_count();
+        let err = runtime.eval_sandboxed(&[1], sandbox_opts(false), |sandbox| loop {
+            sandbox.push_new(Object::new(Unit))?;
+        });
+        assert!(matches!(err, Err(Error::OutOfMemory { .. })));
+        collector.lock().collect().unwrap();
+        assert_eq!(collector.lock().alive_count(), before);
+        let err = runtime.eval_sandboxed(&[], sandbox_opts(false), |sandbox| loop {
+            sandbox.push_context();
+            sandbox.call(1, &[])?;
+        });
+        assert!(matches!(err, Err(Error::StackOverflow)));
+        assert_eq!(runtime.stack_len(), 2);
+
+        let count = runtime
+            .eval_sandboxed(&[1], sandbox_opts(false), |sandbox| {
+                let value = sandbox.get_int(1)?;
+                sandbox.push_int(value * 10)?;
+                sandbox.push_int(value * 100)?;
+                Ok(2)
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(runtime.get_int(2).unwrap(), 20);
+        assert_eq!(runtime.get_int(1).unwrap(), 200);
+    }
+
+    #[test]
+    fn sandbox_copies_or_shares_arguments() {
+        let mut runtime = boot();
+        runtime.push_int(2).unwrap();
+        let set = |sandbox: &mut Runtime| {
+            sandbox.get_as_mut::<IntObject>(1)?.0 = 99;
+            Ok(0)
+        };
+        runtime
+            .eval_sandboxed(&[1], sandbox_opts(false), set)
+            .unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+        runtime
+            .eval_sandboxed(&[1], sandbox_opts(true), set)
+            .unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 99);
+
+        let count = runtime
+            .eval_sandboxed(&[1, 1], sandbox_opts(true), |sandbox| {
+                sandbox.get_as_mut::<IntObject>(1)?.0 += 1;
+                assert_eq!(sandbox.get_int(2)?, 100);
+                Ok(0)
+            })
+            .unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(runtime.get_int(1).unwrap(), 100);
+    }
+
+    #[test]
+    fn sandbox_results_never_leave_holes() {
+        let mut runtime = boot();
+        runtime.push_int(5).unwrap();
+        let err = runtime.eval_sandboxed(&[1], sandbox_opts(true), |_| Ok(1));
+        assert!(matches!(err, Err(Error::NotTransferable)));
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+
+        let count = runtime
+            .eval_sandboxed(&[], sandbox_opts(false), |sandbox| {
+                let address = sandbox.push_new(Object::with_vtable(IntObject(7)))?;
+                sandbox.push_address(address)?;
+                Ok(2)
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(runtime.get_int(1).unwrap(), 7);
+        assert_eq!(runtime.get_int(2).unwrap(), 7);
+        assert_ne!(
+            runtime.load(Source::Local(1)).unwrap(),
+            runtime.load(Source::Local(2)).unwrap()
+        );
+
+        let err = runtime.eval_sandboxed(&[3], sandbox_opts(true), |sandbox| {
+            let _taken = sandbox.take(1)?;
+            Ok(0)
+        });
+        assert!(matches!(err, Err(Error::BusyObject)));
+        assert!(matches!(runtime.get_int(3), Err(Error::Abandoned)));
+    }
+
+    #[test]
+    fn converts_between_numbers_and_strings() {
+        let mut runtime = boot();
+        runtime.push_str("12").unwrap();
+        runtime.parse_int(1).unwrap();
+        assert_eq!(runtime.stack_len(), 2);
+        runtime.unwrap_result(1).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 12);
+        runtime.push_str("x").unwrap();
+        runtime.parse_float(1).unwrap();
+        assert!(!runtime.is_ok(1).unwrap());
+
+        runtime.push_int(i64::MIN).unwrap();
+        runtime.int_to_string(1).unwrap();
+        assert_eq!(runtime.get_str(1).unwrap(), "-9223372036854775808");
+        runtime.int_to_float(2).unwrap();
+        assert_eq!(runtime.get_float(1).unwrap(), i64::MIN as f64);
+        runtime.float_to_int(1, RoundMode::Trunc).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), i64::MIN);
+        runtime.push_float(-2.5).unwrap();
+        runtime.float_to_string(1, Some(2)).unwrap();
+        assert_eq!(runtime.get_str(1).unwrap(), "-2.50");
+        runtime.float_to_string(2, None).unwrap();
+        assert_eq!(runtime.get_str(1).unwrap(), "-2.5");
+        runtime.float_to_int(3, RoundMode::Round).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), -3);
+        runtime.push_float(f64::NAN).unwrap();
+        let stack_len = runtime.stack_len();
+        assert!(matches!(
+            runtime.float_to_int(1, RoundMode::Floor),
+            Err(Error::FloatToInt(_))
+        ));
+        assert_eq!(runtime.stack_len(), stack_len);
+        runtime.push_float(1.5).unwrap();
+        runtime.push_str("{}").unwrap();
+        runtime.format(1, &[2]).unwrap();
+        assert_eq!(runtime.get_str(1).unwrap(), "1.5");
+    }
+
+    #[test]
+    fn suspends_and_resumes() {
+        let run = |suspend: bool| {
+            let mut runtime = boot();
+            runtime.push_int(1).unwrap();
+            runtime.call(1, &[1]).unwrap();
+            runtime.push_int(2).unwrap();
+            runtime.call(1, &[1]).unwrap();
+            runtime.push_int(3).unwrap();
+            if suspend {
+                let object = runtime.take(1).unwrap();
+                let (back, err) = runtime.suspend().err().unwrap();
+                assert!(matches!(err, Error::SuspendWhileTaken));
+                runtime = *back;
+                runtime.fill(1, object).unwrap();
+                let suspended = runtime.suspend().ok().unwrap();
+                assert_eq!(suspended.depth(), 3);
+                runtime = Runtime::resume(suspended);
+            }
+            runtime.back(&[1, 2]).unwrap();
+            runtime.back(&[1, 2, 3]).unwrap();
+            (1..=4)
+                .map(|index| runtime.get_int(index).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    // yields the int at the top of its own stack, counting up until it passes `limit`
+    fn counter(limit: i64) -> impl FnMut(&mut Runtime) -> Result<()> {
+        move |runtime| {
+            let value = runtime.get_int(1)?;
+            if value > limit {
+                return Ok(());
+            }
+            runtime.push_int(value + 1)?;
+            runtime.push_int(value)?;
+            runtime.yield_value(1)?;
+            runtime.pop()
+        }
+    }
+
+    #[test]
+    fn generators_interleave() {
+        let mut runtime = boot();
+        runtime.push_int(1).unwrap();
+        runtime.call_generator(1, &[1], counter(3)).unwrap();
+        runtime.call_generator(2, &[2], counter(2)).unwrap();
+        assert!(matches!(runtime.yield_value(1), Err(Error::NotInGenerator)));
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            for &generator in &[2, 1] {
+                match runtime.resume_generator(generator).unwrap() {
+                    GeneratorStep::Yielded => {
+                        seen.push(runtime.get_int(1).unwrap());
+                        runtime.pop().unwrap();
+                    }
+                    GeneratorStep::Done { .. } => seen.push(0),
+                }
+            }
+        }
+        assert_eq!(seen, vec![1, 1, 2, 2, 3, 0]);
+        assert_eq!(
+            runtime.resume_generator(2).unwrap(),
+            GeneratorStep::Done { pushed: 0 }
+        );
+        assert!(matches!(
+            runtime.resume_generator(1),
+            Err(Error::GeneratorFinished)
+        ));
+        assert!(matches!(
+            runtime.resume_generator(2),
+            Err(Error::GeneratorFinished)
+        ));
+
+        runtime
+            .call_generator(3, &[], |runtime: &mut Runtime| {
+                runtime.push_int(9)?;
+                runtime.back(&[1])
+            })
+            .unwrap();
+        assert_eq!(
+            runtime.resume_generator(1).unwrap(),
+            GeneratorStep::Done { pushed: 1 }
+        );
+        assert_eq!(runtime.get_int(1).unwrap(), 9);
+    }
+
+    #[test]
+    fn yielded_values_survive_collection() {
+        let mut runtime = boot();
+        runtime.push_int(0).unwrap();
+        runtime
+            .call_generator(1, &[], |runtime: &mut Runtime| {
+                runtime.push_new(Object::with_vtable(IntObject(77)))?;
+                runtime.yield_value(1)?;
+                runtime.pop()
+            })
+            .unwrap();
+        runtime.resume_generator(1).unwrap();
+        runtime.collect_now().unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 77);
+    }
+
+    #[test]
+    fn no_tail_call_from_generator_frame() {
+        let mut runtime = boot();
+        runtime.push_int(1).unwrap();
+        let mut step = counter(2);
+        runtime
+            .call_generator(1, &[1], move |runtime: &mut Runtime| {
+                assert!(matches!(
+                    runtime.tail_call(1, &[]),
+                    Err(Error::TailCallInGenerator)
+                ));
+                runtime.call(1, &[])?;
+                runtime.push_context();
+                runtime.tail_call(1, &[])?;
+                runtime.back(&[])?;
+                step(runtime)
+            })
+            .unwrap();
+        for expected in 1..=2 {
+            assert_eq!(runtime.resume_generator(1).unwrap(), GeneratorStep::Yielded);
+            assert_eq!(runtime.get_int(1).unwrap(), expected);
+            runtime.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn detects_waiting_on_own_take() {
+        let (mut runtime, collector) = boot_with();
+        let context = collector
+            .lock()
+            .allocate(Slot::new(Object::new(Unit)))
+            .unwrap();
+        let mut other = RuntimeBuilder::new(collector, context).boot().unwrap();
+        runtime.push_int(1).unwrap();
+        other.push_int(2).unwrap();
+        let object = runtime.take(1).unwrap();
+        assert!(matches!(
+            runtime.wait(1),
+            Err(Error::DeadlockDetected { .. })
+        ));
+        let err = runtime.wait(1).unwrap_err().to_string();
+        let address = runtime.load(Source::Local(1)).unwrap();
+        assert!(err.contains(&format!("{:?}", address)), "{}", err);
+        runtime.fill(1, object).unwrap();
+        let object = runtime.wait(1).unwrap();
+        runtime.fill(1, object).unwrap();
+        let object = other.take(1).unwrap();
+        other.fill(1, object).unwrap();
+    }
+
+    #[test]
+    fn push_address_refuses_collected_objects() {
+        let (mut runtime, collector) = boot_with();
+        let int = runtime.push_new(Object::with_vtable(IntObject(3))).unwrap();
+        runtime.push_address(int.clone()).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 3);
+        assert_eq!(runtime.load(Source::Local(2)).unwrap(), int);
+        let unit = runtime.push_new(Object::new(Unit)).unwrap();
+        for _ in 0..3 {
+            runtime.pop().unwrap();
+        }
+        runtime.push_int(0).unwrap();
+        collector.lock().collect().unwrap();
+        assert!(runtime.push_address(unit).is_err());
+        assert!(runtime.push_address(int).is_err());
+    }
+
+    #[test]
+    fn object_mut_keeps_changes_and_fills_back() {
+        let mut runtime = boot();
+        runtime.push_int(1).unwrap();
+        runtime.push_int(2).unwrap();
+        let failed: Result<()> = runtime.with_object_mut(1, |int: &mut IntObject| {
+            int.0 = 5;
+            Err(Error::Cancelled)
+        });
+        assert!(failed.is_err());
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = runtime.with_object_mut(1, |_: &mut IntObject| -> Result<()> { panic!() });
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+        assert!(matches!(
+            runtime.with_object_mut(1, |_: &mut Unit| Ok(())),
+            Err(Error::TypeMismatch { .. })
+        ));
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+    }
+
+    #[test]
+    fn two_objects_mut_in_either_order() {
+        let mut runtime = boot();
+        runtime.push_int(1).unwrap();
+        runtime.push_int(5).unwrap();
+        runtime
+            .with_two_objects_mut(1, 2, |a: &mut IntObject, b: &mut IntObject| {
+                std::mem::swap(a, b);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        assert_eq!(runtime.get_int(2).unwrap(), 5);
+        runtime
+            .with_two_objects_mut(2, 1, |a: &mut IntObject, b: &mut IntObject| {
+                a.0 += 10;
+                b.0 += 100;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 101);
+        assert_eq!(runtime.get_int(2).unwrap(), 15);
+        assert!(runtime
+            .with_two_objects_mut(1, 1, |_: &mut IntObject, _: &mut IntObject| Ok(()))
+            .is_err());
+        assert!(runtime
+            .with_two_objects_mut(1, 2, |_: &mut IntObject, _: &mut Unit| Ok(()))
+            .is_err());
+        assert_eq!(runtime.get_int(2).unwrap(), 15);
+    }
+
+    #[test]
+    fn address_key_is_the_address_number() {
+        let mut runtime = boot();
+        for value in 0..12 {
+            let address = runtime
+                .push_new(Object::with_vtable(IntObject(value)))
+                .unwrap();
+            let key = address_key(&address);
+            assert_eq!(format!("{:?}", address), format!("Address({})", key));
+        }
+    }
+
+    #[test]
+    fn collect_now_reports_reclaimed() {
+        let (collector, context) = memory(256);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .collector_capacity(256)
+            .boot()
+            .unwrap();
+        let stats = runtime.memory_stats();
+        assert_eq!(stats.capacity, Some(256));
+        for _ in 0..10 {
+            runtime.push_new(Object::new(Unit)).unwrap();
+        }
+        assert_eq!(runtime.memory_stats().allocated, stats.allocated + 10);
+        for _ in 0..10 {
+            runtime.pop().unwrap();
+        }
+        let report = runtime.collect_now().unwrap();
+        assert_eq!(report.reclaimed, 10);
+        assert_eq!(report.alive, stats.allocated);
+
+        runtime.push_int(1).unwrap();
+        runtime.call(1, &[1]).unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.pop().unwrap();
+        runtime.hold_frame(|runtime| {
+            assert_eq!(runtime.collect_now().unwrap().reclaimed, 1);
+            runtime.push_new(Object::new(Unit)).unwrap();
+            runtime.pop().unwrap();
+            assert_eq!(runtime.collect_now().unwrap().reclaimed, 1);
+        });
+        assert_eq!(runtime.collect_now().unwrap().reclaimed, 0);
+        assert_eq!(runtime.get_int(2).unwrap(), 1);
+        runtime.back(&[]).unwrap();
+        assert_eq!(runtime.collect_now().unwrap().reclaimed, 2);
+    }
+
+    fn ints(runtime: &mut Runtime) -> Vec<i64> {
+        let items = runtime.get_as::<ListObject>(1).unwrap().items().to_vec();
+        items
+            .into_iter()
+            .map(|item| {
+                runtime.push_address(item).unwrap();
+                let value = runtime.get_int(1).unwrap();
+                runtime.pop().unwrap();
+                value
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sorts_lists_stably() {
+        let mut runtime = boot();
+        let items: Vec<_> = [3, 1, 2, 1]
+            .iter()
+            .map(|&value| {
+                runtime
+                    .push_new(Object::with_vtable(IntObject(value)))
+                    .unwrap()
+            })
+            .collect();
+        runtime
+            .push_new(Object::new(ListObject::new(items.to_owned())))
+            .unwrap();
+        runtime.sort_list(1).unwrap();
+        assert_eq!(ints(&mut runtime), vec![1, 1, 2, 3]);
+        assert_eq!(
+            runtime.get_as::<ListObject>(1).unwrap().items()[0],
+            items[1]
+        );
+        assert!(runtime
+            .sort_list_by(1, |_, _| Err(Error::Cancelled))
+            .is_err());
+        assert_eq!(ints(&mut runtime), vec![1, 1, 2, 3]);
+        runtime
+            .sort_list_by(1, |a, b| a.compare(b).map(CmpOrdering::reverse))
+            .unwrap();
+        assert_eq!(ints(&mut runtime), vec![3, 2, 1, 1]);
+        assert_eq!(
+            runtime.get_as::<ListObject>(1).unwrap().items()[2],
+            items[1]
+        );
+        assert_eq!(runtime.compare(2, 3).unwrap(), CmpOrdering::Less);
+        assert_eq!(runtime.compare(2, 2).unwrap(), CmpOrdering::Equal);
+        assert_eq!(runtime.hash_of(4).unwrap(), runtime.hash_of(2).unwrap());
+        runtime.push_float(f64::NAN).unwrap();
+        assert!(runtime.compare(1, 1).is_err());
+    }
+
+    #[test]
+    fn dict_looks_up_by_value() {
+        let mut runtime = boot();
+        runtime.push_new(Object::new(ObjectDict::new())).unwrap();
+        runtime.push_new(Object::with_vtable(IntObject(1))).unwrap();
+        runtime.push_str("one").unwrap();
+        runtime.dict_insert(3, 2, 1).unwrap();
+        // an equal key, not the same object
+        runtime.push_new(Object::with_vtable(IntObject(1))).unwrap();
+        assert!(runtime.dict_get(4, 1).unwrap());
+        assert_eq!(runtime.get_str(1).unwrap(), "one");
+        runtime.push_str("two").unwrap();
+        assert!(!runtime.dict_get(6, 1).unwrap());
+        assert_eq!(runtime.get_as::<ObjectDict>(6).unwrap().len(), 1);
+        runtime.push_new(Object::new(Unit)).unwrap();
+        assert!(matches!(
+            runtime.dict_insert(7, 1, 2),
+            Err(Error::NotHashable(_))
+        ));
+        runtime.dict_insert(7, 2, 3).unwrap();
+        assert_eq!(runtime.get_as::<ObjectDict>(7).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn back_keeps_order() {
+        for count in 0..8i64 {
+            let mut runtime = boot();
+            runtime.push_new(Object::new(Unit)).unwrap();
+            runtime.call(1, &[]).unwrap();
+            for value in 0..count {
+                runtime.push_int(value).unwrap();
+            }
+            // index 1 of the callee holds `count - 1`
+            let returned: Vec<usize> = (1..=count as usize).collect();
+            runtime.back(&returned).unwrap();
+            for index in 1..=count as usize {
+                assert_eq!(runtime.get_int(index).unwrap(), count - index as i64);
+            }
+            runtime.call(1, &[]).unwrap();
+            for value in 0..count {
+                runtime.push_int(value).unwrap();
+            }
+            runtime.back_all().unwrap();
+            for index in 1..=count as usize {
+                assert_eq!(runtime.get_int(index).unwrap(), count - index as i64);
+            }
+            assert_eq!(runtime.stack_len(), 1 + 2 * count as usize);
+        }
+    }
+
+    #[test]
+    fn arguments_and_results_in_order() {
+        let mut runtime = boot();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.push_int(3).unwrap();
+        runtime.push_int(2).unwrap();
+        runtime.push_int(1).unwrap();
+        runtime.call(4, &[1, 2, 3]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        assert_eq!(runtime.get_int(3).unwrap(), 3);
+        runtime.back(&[1, 2, 3]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        assert_eq!(runtime.get_int(3).unwrap(), 3);
+    }
+
+    #[test]
+    fn nil_is_one_object() {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let collector: Collector = Arc::new(Mutex::new(RawCollector::new(64)));
+        let mut runtime = RuntimeBuilder::new_with_nil_context(collector)
+            .boot()
+            .unwrap();
+        runtime.push_context();
+        assert!(runtime.is_nil(1).unwrap());
+        runtime.push_nil().unwrap();
+        runtime.push_int(0).unwrap();
+        assert!(runtime.is_nil(2).unwrap());
+        assert!(!runtime.is_nil(1).unwrap());
+        assert!(runtime.is_truthy(1).unwrap());
+        assert!(!runtime.is_truthy(2).unwrap());
+        for _ in 0..3 {
+            runtime.pop().unwrap();
+        }
+        runtime.collect_now().unwrap();
+        runtime.push_nil().unwrap();
+        runtime.push_nil().unwrap();
+        assert!(runtime
+            .with_two_objects_mut(1, 2, |_: &mut NilObject, _: &mut NilObject| Ok(()))
+            .is_err());
+        assert!(runtime.is_nil(1).unwrap());
+        assert!(runtime.is_nil(2).unwrap());
+    }
+
+    #[test]
+    fn call_reserves_before_entering() {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let collector: Collector = Arc::new(Mutex::new(RawCollector::new(8)));
+        let mut runtime = RuntimeBuilder::new_with_nil_context(collector.to_owned())
+            .collector_capacity(8)
+            .boot()
+            .unwrap();
+        // root, nil and the root frame
+        assert_eq!(collector.lock().alive_count(), 3);
+        for value in 0..4 {
+            runtime
+                .push_new(Object::with_vtable(IntObject(value)))
+                .unwrap();
+        }
+        runtime
+            .push_temp(Object::with_vtable(IntObject(9)))
+            .unwrap();
+        // the temporary needs a slot and the frame another, only one is left
+        assert!(matches!(
+            runtime.call(1, &[1]),
+            Err(Error::OutOfMemory { .. })
+        ));
+        assert_eq!(runtime.stack_len(), 5);
+        assert_eq!(runtime.backtrace().len(), 1);
+        assert_eq!(collector.lock().alive_count(), 7);
+        assert_eq!(runtime.get_int(1).unwrap(), 9);
+        runtime.pop().unwrap();
+        runtime.pop().unwrap();
+        runtime.collect_now().unwrap();
+        runtime.call(1, &[1]).unwrap();
+        assert_eq!(runtime.backtrace().len(), 2);
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+        runtime.back(&[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+    }
+
+    #[test]
+    fn failed_tail_call_skips_deferred_steps() {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let collector: Collector = Arc::new(Mutex::new(RawCollector::new(8)));
+        let mut runtime = RuntimeBuilder::new_with_nil_context(collector)
+            .collector_capacity(8)
+            .frame_pool_size(0)
+            .boot()
+            .unwrap();
+        runtime.push_context();
+        runtime.call(1, &[]).unwrap();
+        let ran = Rc::new(Cell::new(0));
+        let counted = ran.to_owned();
+        runtime.defer(move |runtime| {
+            counted.set(counted.get() + 1);
+            // the frame of the tail call is not on the stack yet
+            runtime.collect_now().map(drop)
+        });
+        for value in 0..3 {
+            runtime
+                .push_new(Object::with_vtable(IntObject(value)))
+                .unwrap();
+        }
+        runtime
+            .push_temp(Object::with_vtable(IntObject(9)))
+            .unwrap();
+        assert!(matches!(
+            runtime.tail_call(1, &[1]),
+            Err(Error::OutOfMemory { .. })
+        ));
+        assert_eq!(ran.get(), 0);
+        assert_eq!(runtime.stack_len(), 4);
+        assert_eq!(runtime.backtrace().len(), 2);
+        runtime.pop().unwrap();
+        runtime.tail_call(1, &[1]).unwrap();
+        assert_eq!(ran.get(), 1);
+        assert_eq!(runtime.backtrace().len(), 2);
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+        runtime.back(&[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 2);
+    }
+
+    #[test]
+    fn abandons_outstanding_takes() {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let collector: Collector = Arc::new(Mutex::new(RawCollector::new(64)));
+        let boot = || {
+            RuntimeBuilder::new_with_nil_context(collector.to_owned())
+                .boot()
+                .unwrap()
+        };
+        let mut owner = boot();
+        let mut other = boot();
+        let first = owner.push_new(Object::with_vtable(IntObject(1))).unwrap();
+        let second = owner.push_new(Object::with_vtable(IntObject(2))).unwrap();
+        let taken = owner.take(2).unwrap();
+        let object = owner.take(1).unwrap();
+        owner.fill(1, object).unwrap();
+        other.push_address(first).unwrap();
+        other.push_address(second.to_owned()).unwrap();
+        assert_eq!(owner.abandon(), 1);
+        drop(taken);
+        assert!(matches!(other.get_int(2), Err(Error::Abandoned)));
+        assert!(matches!(other.get_int(2), Err(Error::Abandoned)));
+        assert_eq!(other.get_int(1).unwrap(), 2);
+        assert_eq!(boot().abandon(), 0);
+
+        let mut dropped = boot();
+        dropped.push_address(second).unwrap();
+        let waited = dropped.wait(1).unwrap();
+        drop(dropped);
+        drop(waited);
+        assert!(matches!(other.get_int(1), Err(Error::Abandoned)));
+
+        let mut memory = collector.lock();
+        memory.collect().unwrap();
+        let alive = memory.alive_count();
+        drop(memory);
+        drop(other);
+        let mut memory = collector.lock();
+        memory.collect().unwrap();
+        // the frame, nil and both ints of `other` are gone, its root stays in the chain
+        assert_eq!(memory.alive_count(), alive - 4);
+    }
+
+    #[test]
+    fn abandons_takes_of_returned_frames() {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let collector: Collector = Arc::new(Mutex::new(RawCollector::new(64)));
+        let mut owner = RuntimeBuilder::new_with_nil_context(collector.to_owned())
+            .boot()
+            .unwrap();
+        let mut other = RuntimeBuilder::new_with_nil_context(collector)
+            .boot()
+            .unwrap();
+        let int = owner.push_new(Object::with_vtable(IntObject(1))).unwrap();
+        other.push_address(int).unwrap();
+        owner.call(1, &[1]).unwrap();
+        let taken = owner.take(1).unwrap();
+        owner.back(&[]).unwrap();
+        assert!(matches!(owner.fill(1, taken), Err(Error::FillWithoutTake)));
+        match owner.suspend() {
+            Err((owner, Error::SuspendWhileTaken)) => assert_eq!(owner.abandon(), 1),
+            _ => panic!(),
+        }
+        assert!(matches!(other.get_int(1), Err(Error::Abandoned)));
+    }
+
+    #[test]
+    fn profiles_calls_per_context() {
+        let (collector, _) = memory(256);
+        let mut runtime = RuntimeBuilder::new_with_nil_context(collector.to_owned())
+            .enable_profiling(true)
+            .boot()
+            .unwrap();
+        let outer = runtime.push_new(Object::new(Unit)).unwrap();
+        let inner = runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.call(2, &[1]).unwrap();
+        for _ in 0..10 {
+            runtime.call(1, &[]).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+            runtime.back(&[]).unwrap();
+        }
+        runtime.back(&[]).unwrap();
+        let report = runtime.profile_report();
+        assert_eq!(report.len(), 2);
+        let outer = report.iter().find(|p| p.context == outer).unwrap();
+        let inner = report.iter().find(|p| p.context == inner).unwrap();
+        assert_eq!(outer.calls, 1);
+        assert_eq!(inner.calls, 10);
+        assert!(outer.exclusive < outer.inclusive);
+        assert!(outer.inclusive >= inner.inclusive);
+        // most exclusive time first
+        assert_eq!(report[0].context, inner.context);
+        runtime.reset_profile();
+        assert!(runtime.profile_report().is_empty());
+
+        let mut runtime = RuntimeBuilder::new_with_nil_context(collector)
+            .boot()
+            .unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.call(1, &[]).unwrap();
+        runtime.back(&[]).unwrap();
+        assert!(runtime.profile_report().is_empty());
+    }
+
+    #[test]
+    fn reads_and_writes_in_place() {
+        let mut runtime = boot();
+        runtime.push_int(5).unwrap();
+        runtime
+            .push_temp(Object::with_vtable(IntObject(7)))
+            .unwrap();
+        assert_eq!(runtime.read_at(1, |int: &IntObject| Ok(int.0)).unwrap(), 7);
+        assert_eq!(runtime.read_at(2, |int: &IntObject| Ok(int.0)).unwrap(), 5);
+        for index in 1..=2 {
+            runtime
+                .write_at(index, |int: &mut IntObject| {
+                    int.0 += 1;
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert_eq!(runtime.get_int(1).unwrap(), 8);
+        assert_eq!(runtime.get_int(2).unwrap(), 6);
+        runtime.push_str("s").unwrap();
+        assert!(matches!(
+            runtime.read_at(1, |int: &IntObject| Ok(int.0)),
+            Err(Error::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            runtime.read_at(9, |int: &IntObject| Ok(int.0)),
+            Err(Error::ExhaustedFrame)
+        ));
+        assert!(matches!(
+            runtime.write_at(2, |_: &mut IntObject| -> Result<()> {
+                Err(Error::Cancelled)
+            }),
+            Err(Error::Cancelled)
+        ));
+        // the caller's frame is stored meanwhile
+        runtime.call(1, &[2, 3]).unwrap();
+        assert_eq!(runtime.read_at(2, |int: &IntObject| Ok(int.0)).unwrap(), 6);
+        runtime
+            .write_at(1, |int: &mut IntObject| {
+                int.0 = 0;
+                Ok(())
+            })
+            .unwrap();
+        runtime.back(&[1]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn small_ints_stay_off_the_heap() {
+        let (mut runtime, collector) = boot_with();
+        let alive = collector.lock().alive_count();
+        runtime.push_small(0).unwrap();
+        for _ in 0..100_000 {
+            runtime
+                .write_at(1, |int: &mut IntObject| {
+                    int.0 += 1;
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert_eq!(runtime.get_int(1).unwrap(), 100_000);
+        assert_eq!(collector.lock().alive_count(), alive);
+        assert!(runtime.read_at(1, |_: &Unit| Ok(())).is_err());
+        runtime.push_int(2).unwrap();
+        runtime.push_small(3).unwrap();
+        assert_eq!(runtime.get_int(2).unwrap() + runtime.get_int(1).unwrap(), 5);
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.call(1, &[2, 4]).unwrap();
+        // the int, the context, both small ints promoted and the callee frame
+        assert_eq!(collector.lock().alive_count(), alive + 5);
+        assert_eq!(runtime.get_int(1).unwrap(), 3);
+        assert_eq!(runtime.get_int(2).unwrap(), 100_000);
+        runtime.back(&[]).unwrap();
+        // promoted in place, so the entry refers to the same object from now on
+        runtime.materialize(2).unwrap();
+        assert_eq!(collector.lock().alive_count(), alive + 5);
+        runtime.push_small(7).unwrap();
+        runtime.push_small(7).unwrap();
+        runtime.materialize(1).unwrap();
+        runtime.materialize(2).unwrap();
+        assert!(runtime
+            .with_two_objects_mut(1, 2, |a: &mut IntObject, b: &mut IntObject| Ok(a.0 == b.0))
+            .unwrap());
+        assert_eq!(runtime.compare(1, 2).unwrap(), CmpOrdering::Equal);
+    }
+
+    #[test]
+    fn pinned_objects_stay_in_place() {
+        let mut runtime = boot();
+        runtime.push_int(11).unwrap();
+        let pin = runtime.pin(1).unwrap();
+        assert!(matches!(runtime.take(1), Err(Error::Pinned)));
+        assert!(matches!(runtime.wait(1), Err(Error::Pinned)));
+        assert!(matches!(
+            runtime.replace(1, Object::with_vtable(IntObject(12))),
+            Err(Error::Pinned)
+        ));
+        // accessed in place, so the pointer stays valid
+        runtime.get_as_mut::<IntObject>(1).unwrap().0 += 1;
+        assert_eq!(unsafe { (*pin.as_ptr::<IntObject>().unwrap()).0 }, 12);
+        drop(pin);
+        let object = runtime.take(1).unwrap();
+        runtime.fill(1, object).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "runtime dropped with live pins")]
+    fn dropping_with_live_pins_panics() {
+        let mut runtime = boot();
+        runtime.push_int(11).unwrap();
+        let _pin = runtime.pin(1).unwrap();
+        drop(runtime);
+    }
+
+    #[test]
+    fn detects_waiting_on_held_handle() {
+        let (mut runtime, collector) = boot_with();
+        let context = collector
+            .lock()
+            .allocate(Slot::new(Object::new(Unit)))
+            .unwrap();
+        let mut other = RuntimeBuilder::new(collector, context).boot().unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        let address = runtime.load(Source::Local(1)).unwrap();
+        other.push_address(address).unwrap();
+        let waited = runtime
+            .with_object_mut(1, |_: &mut Unit| {
+                let err = other.wait(1).unwrap_err();
+                assert!(err.to_string().contains("Unit"), "{}", err);
+                Ok(err)
+            })
+            .unwrap();
+        assert!(matches!(waited, Error::DeadlockDetected { .. }));
+        // released along with the handle
+        let object = other.wait(1).unwrap();
+        other.fill(1, object).unwrap();
+
+        // the same for a frame cached by the other runtime
+        let frame = other.frame_stack.last().unwrap().to_owned();
+        runtime.push_address(frame).unwrap();
+        other.hold_frame(|_| {
+            assert!(matches!(
+                runtime.wait(1),
+                Err(Error::DeadlockDetected { .. })
+            ));
+        });
+        let object = runtime.wait(1).unwrap();
+        runtime.fill(1, object).unwrap();
+    }
+
+    #[test]
+    fn deferred_steps_keep_their_addresses() {
+        let mut runtime = boot();
+        runtime.push_int(0).unwrap();
+        runtime.call(1, &[]).unwrap();
+        let kept = runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.pop().unwrap();
+        let ran = Rc::new(Cell::new(false));
+        let step_ran = ran.to_owned();
+        let address = kept.to_owned();
+        runtime.defer_keeping(vec![kept.to_owned()], move |runtime| {
+            runtime.push_address(address)?;
+            runtime.get_as::<Unit>(1)?;
+            step_ran.set(true);
+            Ok(())
+        });
+        assert_eq!(runtime.collect_now().unwrap().reclaimed, 0);
+        runtime.back(&[]).unwrap();
+        assert!(ran.get());
+        // released along with the frame
+        runtime.collect_now().unwrap();
+        assert!(runtime.push_address(kept).is_err());
+    }
+
+    #[test]
+    fn finalizes_dropped_objects() {
+        struct Resource(Rc<Cell<usize>>);
+
+        impl Keep for Resource {
+            fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+        }
+
+        impl Finalize for Resource {
+            fn finalize(&mut self) {
+                self.0.set(self.0.get() + 1)
+            }
+        }
+
+        crate::capability!(Resource: finalize);
+
+        let finalized = Rc::new(Cell::new(0));
+        let resource = || Object::with_vtable(Resource(finalized.to_owned()));
+        let (mut runtime, collector) = boot_with();
+        runtime.push_new(resource()).unwrap();
+        runtime.pop().unwrap();
+        assert_eq!(finalized.get(), 0);
+        runtime.collect_now().unwrap();
+        assert_eq!(finalized.get(), 1);
+
+        runtime.push_new(resource()).unwrap();
+        let replaced = runtime.replace(1, Object::new(Unit)).unwrap();
+        drop(replaced);
+        assert_eq!(finalized.get(), 2);
+
+        // the slot gets an abandoned marker, and the object is finalized by its holder
+        let context = runtime.context_address();
+        let mut other = RuntimeBuilder::new(collector, context).boot().unwrap();
+        other.push_new(resource()).unwrap();
+        let taken = other.take(1).unwrap();
+        assert_eq!(other.abandon(), 1);
+        assert_eq!(finalized.get(), 2);
+        drop(taken);
+        assert_eq!(finalized.get(), 3);
+        runtime.collect_now().unwrap();
+        assert_eq!(finalized.get(), 3);
     }
 }
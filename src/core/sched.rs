@@ -0,0 +1,219 @@
+//
+
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+pub trait Scheduler {
+    fn yield_point(&self, tag: &str);
+
+    // a yield point whose operation `f` has to happen in the order the point is recorded
+    // or replayed in, as for atomic operations on shared objects
+    fn atomic_point(&self, tag: &str, f: &mut dyn FnMut()) {
+        self.yield_point(tag);
+        f()
+    }
+
+    // monotonic, from an arbitrary start
+    fn now(&self) -> Duration {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed()
+    }
+
+    // a scheduler running tasks on a pool would park the task here instead
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+pub struct NoopScheduler;
+
+impl Scheduler for NoopScheduler {
+    #[inline(always)]
+    fn yield_point(&self, _tag: &str) {}
+}
+
+// threads are told apart by name, since `ThreadId`s differ between runs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct YieldEvent {
+    pub thread: String,
+    pub tag: String,
+}
+
+impl YieldEvent {
+    fn current(tag: &str) -> Self {
+        Self {
+            thread: thread::current().name().unwrap_or("<unnamed>").to_owned(),
+            tag: tag.to_owned(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RecordingScheduler {
+    events: Mutex<Vec<YieldEvent>>,
+}
+
+impl RecordingScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<YieldEvent> {
+        self.events.lock().to_owned()
+    }
+}
+
+impl Scheduler for RecordingScheduler {
+    fn yield_point(&self, tag: &str) {
+        self.events.lock().push(YieldEvent::current(tag));
+    }
+
+    fn atomic_point(&self, tag: &str, f: &mut dyn FnMut()) {
+        let mut events = self.events.lock();
+        events.push(YieldEvent::current(tag));
+        f()
+    }
+}
+
+// blocks every yield point until it is the next recorded one, so a run that diverges
+// from the recording before its end blocks as well; yield points after the end pass
+pub struct ReplayScheduler {
+    events: Vec<YieldEvent>,
+    position: Mutex<usize>,
+    turn: Condvar,
+}
+
+impl ReplayScheduler {
+    pub fn new(events: Vec<YieldEvent>) -> Self {
+        Self {
+            events,
+            position: Mutex::new(0),
+            turn: Condvar::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        *self.position.lock() == self.events.len()
+    }
+}
+
+impl Scheduler for ReplayScheduler {
+    fn yield_point(&self, tag: &str) {
+        self.atomic_point(tag, &mut || {})
+    }
+
+    fn atomic_point(&self, tag: &str, f: &mut dyn FnMut()) {
+        let event = YieldEvent::current(tag);
+        let mut position = self.position.lock();
+        while *position < self.events.len() && self.events[*position] != event {
+            self.turn.wait(&mut position);
+        }
+        f();
+        if *position < self.events.len() {
+            *position += 1;
+            self.turn.notify_all();
+        }
+    }
+}
+
+// sleeping only moves the clock forward, so tests don't wait for real
+#[derive(Default)]
+pub struct VirtualTimeScheduler {
+    now: Mutex<Duration>,
+}
+
+impl VirtualTimeScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock() += duration;
+    }
+}
+
+impl Scheduler for VirtualTimeScheduler {
+    fn yield_point(&self, _tag: &str) {}
+
+    fn now(&self) -> Duration {
+        *self.now.lock()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::core::object::Object;
+    use crate::core::runtime::tests::{memory, Unit};
+    use crate::core::runtime::RuntimeBuilder;
+
+    type SharedScheduler = Arc<dyn Scheduler + Send + Sync>;
+
+    fn work(scheduler: SharedScheduler) {
+        let (collector, context) = memory(64);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .with_scheduler(scheduler)
+            .boot()
+            .unwrap();
+        runtime.push_new(Object::new(Unit)).unwrap();
+        runtime.call(1, &[1]).unwrap();
+        let object = runtime.take(1).unwrap();
+        runtime.fill(1, object).unwrap();
+        runtime.back(&[]).unwrap();
+    }
+
+    fn run_named(scheduler: SharedScheduler) {
+        let handles: Vec<_> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                let scheduler = scheduler.to_owned();
+                thread::Builder::new()
+                    .name(name.to_string())
+                    .spawn(move || work(scheduler))
+                    .unwrap()
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    struct Both(Arc<ReplayScheduler>, Arc<RecordingScheduler>);
+
+    impl Scheduler for Both {
+        fn yield_point(&self, tag: &str) {
+            self.0.yield_point(tag);
+            self.1.yield_point(tag);
+        }
+    }
+
+    #[test]
+    fn replays_recorded_order() {
+        let recording = Arc::new(RecordingScheduler::new());
+        run_named(recording.to_owned());
+        let events = recording.events();
+        assert_eq!(events.len(), 8, "{:?}", events);
+
+        let mut forced: Vec<_> = events
+            .iter()
+            .filter(|event| event.thread == "b")
+            .cloned()
+            .collect();
+        forced.extend(events.iter().filter(|event| event.thread == "a").cloned());
+        let replay = Arc::new(ReplayScheduler::new(forced.to_owned()));
+        let check = Arc::new(RecordingScheduler::new());
+        run_named(Arc::new(Both(replay.to_owned(), check.to_owned())));
+        assert!(replay.is_finished());
+        assert_eq!(check.events().len(), forced.len());
+    }
+}
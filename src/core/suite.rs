@@ -0,0 +1,52 @@
+//
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::core::error::{Error, Result};
+use crate::core::runtime::Runtime;
+
+// a set of globals installed together, after the suites it depends on
+pub trait BuiltinSuite {
+    fn name(&self) -> &str;
+    fn dependencies(&self) -> &[&str];
+    fn install(&self, runtime: &mut Runtime) -> Result<()>;
+}
+
+pub type Suites = HashMap<String, Arc<dyn BuiltinSuite>>;
+
+// the requested suites and everything they depend on, each after its dependencies
+pub fn resolve(suites: &Suites, requested: &[&str]) -> Result<Vec<Arc<dyn BuiltinSuite>>> {
+    let mut order = Vec::new();
+    let mut path = Vec::new();
+    for name in requested {
+        visit(suites, name, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+fn visit(
+    suites: &Suites,
+    name: &str,
+    path: &mut Vec<String>,
+    order: &mut Vec<Arc<dyn BuiltinSuite>>,
+) -> Result<()> {
+    if order.iter().any(|suite| suite.name() == name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|visiting| visiting == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name.to_owned());
+        return Err(Error::SuiteCycle(cycle));
+    }
+    let suite = suites
+        .get(name)
+        .ok_or_else(|| Error::UnknownSuite(name.to_owned()))?;
+    path.push(name.to_owned());
+    for dependency in suite.dependencies() {
+        visit(suites, dependency, path, order)?;
+    }
+    path.pop();
+    order.push(suite.to_owned());
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//
+
+use std::sync::Arc;
+
+use hulunbuir::Address;
+use parking_lot::Mutex;
+
+// a snapshot which owns all of its data, so that it can be sent to a monitor thread
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeView {
+    // innermost frame first
+    pub frames: Vec<FrameView>,
+    pub alive_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameView {
+    pub address: Address,
+    // type names from stack index 1 on, objects taken out by someone are shown as `BUSY`
+    pub stack: Vec<&'static str>,
+}
+
+pub const BUSY: &str = "<busy>";
+
+impl RuntimeView {
+    // 0 for the default view, which has no frames at all
+    pub fn depth(&self) -> usize {
+        self.frames.len().saturating_sub(1)
+    }
+}
+
+// updated by the runtime on every call and return
+#[derive(Clone, Default)]
+pub struct ViewHandle {
+    view: Arc<Mutex<RuntimeView>>,
+}
+
+impl ViewHandle {
+    pub fn get(&self) -> RuntimeView {
+        self.view.lock().to_owned()
+    }
+
+    pub(crate) fn publish(&self, view: RuntimeView) {
+        *self.view.lock() = view;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_view_has_no_depth() {
+        assert_eq!(RuntimeView::default().depth(), 0);
+        assert_eq!(ViewHandle::default().get().depth(), 0);
+    }
+}
@@ -0,0 +1,383 @@
+//
+
+use std::collections::HashSet;
+
+use crate::core::error::Result;
+use crate::core::object::Object;
+use crate::core::runtime::Collector;
+
+use hulunbuir::{slot::Take, Address, Error as CollectorError, Keep};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seen {
+    First,
+    // reached again through another path, children are not walked twice
+    Again,
+    // reached again through one of its own descendants
+    Cycle,
+}
+
+pub struct WalkInfo<'a> {
+    pub depth: usize,
+    pub parent: Option<Address>,
+    pub seen: Seen,
+    // `None` for objects seen before, and for objects taken out by someone else, whose
+    // children are skipped instead of waiting for them
+    pub object: Option<&'a Object>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkControl {
+    Continue,
+    SkipChildren,
+    Stop,
+}
+
+// depth-first and iterative, so that long chains don't overflow the Rust stack
+pub fn walk<F>(memory: &Collector, start: Address, mut visit: F) -> Result<()>
+where
+    F: FnMut(&Address, &WalkInfo) -> WalkControl,
+{
+    let mut visited = HashSet::new();
+    let mut path: Vec<Address> = Vec::new();
+    let mut stack = vec![(start, 0, None)];
+    while let Some((address, depth, parent)) = stack.pop() {
+        path.truncate(depth);
+        if visited.contains(&address) {
+            let seen = if path.contains(&address) {
+                Seen::Cycle
+            } else {
+                Seen::Again
+            };
+            let info = WalkInfo {
+                depth,
+                parent,
+                seen,
+                object: None,
+            };
+            if visit(&address, &info) == WalkControl::Stop {
+                return Ok(());
+            }
+            continue;
+        }
+        visited.insert(address.to_owned());
+
+        let take = memory.lock().take(&address)?;
+        let object = match take {
+            Take::Free(object) => Some(object),
+            Take::Busy(_) => None,
+        };
+        let info = WalkInfo {
+            depth,
+            parent: parent.to_owned(),
+            seen: Seen::First,
+            object: object.as_ref(),
+        };
+        let control = visit(&address, &info);
+        let object = match object {
+            Some(object) => object,
+            None if control == WalkControl::Stop => return Ok(()),
+            None => continue,
+        };
+        let mut children = Vec::new();
+        if control == WalkControl::Continue {
+            object.with_keep(|keep_list| children = keep_list.to_vec());
+        }
+        memory.lock().fill(&address, object)?;
+        if control == WalkControl::Stop {
+            return Ok(());
+        }
+        for child in children.into_iter().rev() {
+            stack.push((child, depth + 1, Some(address.to_owned())));
+        }
+        path.push(address);
+    }
+    Ok(())
+}
+
+pub fn dump_graph(memory: &Collector, root: Address) -> Result<String> {
+    let mut dump = String::new();
+    walk(memory, root, |address, info| {
+        let indent = "  ".repeat(info.depth);
+        let line = match (info.seen, info.object) {
+            (Seen::Cycle, _) => format!("{}{:?} <cycle>\n", indent, address),
+            (Seen::Again, _) => format!("{}{:?} <shared>\n", indent, address),
+            (Seen::First, Some(object)) => format!("{}{:?} {:?}\n", indent, address, object),
+            (Seen::First, None) => format!("{}{:?} <busy>\n", indent, address),
+        };
+        dump.push_str(&line);
+        WalkControl::Continue
+    })?;
+    Ok(dump)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapEntry {
+    pub address: Address,
+    // `None` and no children for an object taken out by someone else
+    pub type_name: Option<&'static str>,
+    pub children: Vec<Address>,
+}
+
+// every object reachable from `start`, once each and in walking order
+pub fn entries(memory: &Collector, start: Address) -> Result<Vec<HeapEntry>> {
+    let mut entries = Vec::new();
+    walk(memory, start, |address, info| {
+        if info.seen == Seen::First {
+            let mut children = Vec::new();
+            if let Some(object) = info.object {
+                object.with_keep(|keep_list| children = keep_list.to_vec());
+            }
+            entries.push(HeapEntry {
+                address: address.to_owned(),
+                type_name: info.object.map(Object::type_name),
+                children,
+            });
+        }
+        WalkControl::Continue
+    })?;
+    Ok(entries)
+}
+
+// counts `start` itself, and objects taken out by someone else without their children
+pub fn reachable_from(memory: &Collector, start: Address) -> Result<usize> {
+    let mut count = 0;
+    walk(memory, start, |_, info| {
+        if info.seen == Seen::First {
+            count += 1;
+        }
+        WalkControl::Continue
+    })?;
+    Ok(count)
+}
+
+// the objects reachable from `start` which keep `target`, each listed once
+pub fn find_referrers(
+    memory: &Collector,
+    start: Address,
+    target: &Address,
+) -> Result<Vec<Address>> {
+    let mut referrers = Vec::new();
+    walk(memory, start, |address, info| {
+        if let Some(parent) = &info.parent {
+            if address == target && !referrers.contains(parent) {
+                referrers.push(parent.to_owned());
+            }
+        }
+        WalkControl::Continue
+    })?;
+    Ok(referrers)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapViolation {
+    // an address which has no object behind it
+    pub address: Address,
+    // the object keeping it and its type name, `None` for the start address
+    pub holder: Option<(Address, &'static str)>,
+}
+
+// collects every dangling address reachable from `start`; the children of objects taken
+// out by someone are not checked
+pub fn validate(memory: &Collector, start: Address) -> Result<Vec<HeapViolation>> {
+    let mut violations = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(start, None)];
+    while let Some((address, holder)) = stack.pop() {
+        if !visited.insert(address.to_owned()) {
+            continue;
+        }
+        let take = memory.lock().take(&address);
+        let object = match take {
+            Ok(Take::Free(object)) => object,
+            Ok(Take::Busy(_)) => continue,
+            Err(CollectorError::InvalidAddress) => {
+                violations.push(HeapViolation { address, holder });
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let type_name = object.type_name();
+        object.with_keep(|keep_list| {
+            for child in keep_list {
+                stack.push((child.to_owned(), Some((address.to_owned(), type_name))));
+            }
+        });
+        memory.lock().fill(&address, object)?;
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::core::runtime::tests::{boot_with, memory, Unit};
+    use crate::core::runtime::Source;
+    use crate::objects::int::IntObject;
+    use crate::objects::list::ListObject;
+
+    use hulunbuir::slot::Slot;
+
+    fn allocate(memory: &Collector, object: Object) -> Address {
+        memory.lock().allocate(Slot::new(object)).unwrap()
+    }
+
+    fn count_visits<F>(memory: &Collector, start: &Address, mut control: F) -> usize
+    where
+        F: FnMut(usize, &WalkInfo) -> WalkControl,
+    {
+        let mut visits = 0;
+        walk(memory, start.to_owned(), |_, info| {
+            visits += 1;
+            control(visits, info)
+        })
+        .unwrap();
+        visits
+    }
+
+    #[test]
+    fn walks_deep_chains() {
+        let (memory, _) = memory(1 << 16);
+        let mut top = allocate(&memory, Object::with_vtable(IntObject(0)));
+        for _ in 0..10000 {
+            top = allocate(&memory, Object::new(ListObject::new(vec![top])));
+        }
+        let mut deepest = 0;
+        let visits = count_visits(&memory, &top, |_, info| {
+            deepest = deepest.max(info.depth);
+            WalkControl::Continue
+        });
+        assert_eq!((visits, deepest), (10001, 10000));
+        let visits = count_visits(&memory, &top, |_, info| {
+            if info.depth == 5 {
+                WalkControl::SkipChildren
+            } else {
+                WalkControl::Continue
+            }
+        });
+        assert_eq!(visits, 6);
+        let visits = count_visits(&memory, &top, |visits, _| {
+            if visits == 3 {
+                WalkControl::Stop
+            } else {
+                WalkControl::Continue
+            }
+        });
+        assert_eq!(visits, 3);
+    }
+
+    #[test]
+    fn dumps_shared_and_cyclic_objects() {
+        let (memory, _) = memory(64);
+        let leaf = allocate(&memory, Object::with_vtable(IntObject(7)));
+        let left = allocate(&memory, Object::new(ListObject::new(vec![leaf.to_owned()])));
+        let right = allocate(&memory, Object::new(ListObject::new(vec![leaf])));
+        let root = allocate(
+            &memory,
+            Object::new(ListObject::new(vec![left.to_owned(), right])),
+        );
+        let mut list = match memory.lock().take(&left).unwrap() {
+            Take::Free(list) => list,
+            Take::Busy(_) => unreachable!(),
+        };
+        list.downcast_mut::<ListObject>()
+            .unwrap()
+            .push(left.to_owned());
+        memory.lock().fill(&left, list).unwrap();
+        let dump = dump_graph(&memory, root).unwrap();
+        assert!(dump.contains("<cycle>"), "{}", dump);
+        assert!(dump.contains("<shared>"), "{}", dump);
+        assert!(dump.contains("IntObject(7)"), "{}", dump);
+    }
+
+    struct Holder(Address);
+
+    impl Keep for Holder {
+        fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+            f(&[self.0.to_owned()])
+        }
+    }
+
+    #[test]
+    fn validates_dangling_addresses() {
+        let (mut runtime, collector) = boot_with();
+        runtime.push_int(1).unwrap();
+        let kept = allocate(&collector, Object::new(Unit));
+        runtime.push_new(Object::new(Holder(kept))).unwrap();
+        assert!(runtime.validate().unwrap().is_empty());
+
+        let (memory, _) = memory(8);
+        let ghost = allocate(&memory, Object::new(Unit));
+        memory.lock().collect().unwrap();
+        let holder = allocate(&memory, Object::new(Holder(ghost.to_owned())));
+        let violations = validate(&memory, holder.to_owned()).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].address, ghost);
+        let (address, type_name) = violations[0].holder.to_owned().unwrap();
+        assert_eq!(address, holder);
+        assert!(type_name.ends_with("Holder"));
+    }
+
+    #[test]
+    fn inspects_the_heap() {
+        let (memory, _) = memory(64);
+        let leaf = allocate(&memory, Object::with_vtable(IntObject(7)));
+        let left = allocate(&memory, Object::new(Holder(leaf.to_owned())));
+        let right = allocate(&memory, Object::new(ListObject::new(vec![leaf.to_owned()])));
+        let root = allocate(
+            &memory,
+            Object::new(ListObject::new(vec![left.to_owned(), right.to_owned()])),
+        );
+        allocate(&memory, Object::new(Unit));
+        let listed = entries(&memory, root.to_owned()).unwrap();
+        assert_eq!(listed.len(), 4);
+        assert_eq!(listed[0].address, root);
+        assert_eq!(listed[0].children, [left.to_owned(), right.to_owned()]);
+        assert!(listed[1].type_name.unwrap().ends_with("Holder"));
+        assert_eq!(listed[1].children, [leaf.to_owned()]);
+        let busy = match memory.lock().take(&right).unwrap() {
+            Take::Free(list) => list,
+            Take::Busy(_) => unreachable!(),
+        };
+        // still counted, but its children are not walked
+        let listed = entries(&memory, root.to_owned()).unwrap();
+        assert_eq!(listed.len(), 4);
+        assert_eq!(listed[3].address, right);
+        assert_eq!((listed[3].type_name, listed[3].children.len()), (None, 0));
+        memory.lock().fill(&right, busy).unwrap();
+        let referrers = find_referrers(&memory, root.to_owned(), &leaf).unwrap();
+        assert_eq!(referrers, [left, right]);
+        assert!(find_referrers(&memory, root.to_owned(), &root)
+            .unwrap()
+            .is_empty());
+        assert_eq!(reachable_from(&memory, root).unwrap(), 4);
+    }
+
+    #[test]
+    fn reachable_count_matches_survivors() {
+        let (mut runtime, collector) = boot_with();
+        runtime.push_int(1).unwrap();
+        let kept = allocate(&collector, Object::new(Unit));
+        runtime
+            .push_new(Object::new(Holder(kept.to_owned())))
+            .unwrap();
+        for _ in 0..10 {
+            allocate(&collector, Object::new(Unit));
+        }
+        collector.lock().collect().unwrap();
+        assert_eq!(
+            runtime.reachable_count().unwrap(),
+            collector.lock().alive_count()
+        );
+        assert!(runtime
+            .heap_entries()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.address == kept));
+        runtime.push_address(kept).unwrap();
+        let holder = runtime.load(Source::Local(2)).unwrap();
+        assert_eq!(runtime.find_referrers(1).unwrap().len(), 2);
+        assert!(runtime.find_referrers(1).unwrap().contains(&holder));
+    }
+}
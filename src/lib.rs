@@ -1,7 +1,5 @@
 //
 
-extern crate failure;
-#[macro_use] extern crate failure_derive;
-
 pub mod core;
-// pub mod objects;
+pub mod objects;
+pub mod testing;
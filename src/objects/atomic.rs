@@ -0,0 +1,79 @@
+//
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use hulunbuir::{Address, Keep};
+
+// clones share the counter, so one can be handed to runtimes on other threads; every
+// operation takes `&self`, so holding a shared handle is enough
+#[derive(Debug, Clone, Default)]
+pub struct AtomicIntObject(Arc<AtomicI64>);
+
+impl Keep for AtomicIntObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+crate::capability!(AtomicIntObject: debug, clone);
+
+impl AtomicIntObject {
+    pub fn new(value: i64) -> Self {
+        Self(Arc::new(AtomicI64::new(value)))
+    }
+
+    pub fn load(&self) -> i64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn store(&self, value: i64) {
+        self.0.store(value, Ordering::SeqCst)
+    }
+
+    pub fn fetch_add(&self, value: i64) -> i64 {
+        self.0.fetch_add(value, Ordering::SeqCst)
+    }
+
+    pub fn fetch_sub(&self, value: i64) -> i64 {
+        self.0.fetch_sub(value, Ordering::SeqCst)
+    }
+
+    // the previous value either way
+    pub fn compare_exchange(&self, expected: i64, new: i64) -> Result<i64, i64> {
+        self.0
+            .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use crate::core::object::Object;
+    use crate::core::runtime::tests::boot;
+
+    #[test]
+    fn counts_across_runtimes() {
+        let counter = AtomicIntObject::new(0);
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let counter = counter.to_owned();
+                thread::spawn(move || {
+                    let mut runtime = boot();
+                    runtime.push_new(Object::with_vtable(counter)).unwrap();
+                    for _ in 0..10000 {
+                        runtime.get_as::<AtomicIntObject>(1).unwrap().fetch_add(1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counter.load(), 80000);
+        assert_eq!(counter.compare_exchange(1, 2), Err(80000));
+        assert_eq!(counter.compare_exchange(80000, 2), Ok(80000));
+        assert_eq!(counter.fetch_sub(2), 2);
+    }
+}
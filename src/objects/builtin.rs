@@ -0,0 +1,657 @@
+//
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::core::error::Result;
+use crate::core::object::Object;
+use crate::core::runtime::Runtime;
+use crate::core::suite::{BuiltinSuite, Suites};
+use crate::objects::class::ClassObject;
+use crate::objects::float::RoundMode;
+use crate::objects::list::ListObject;
+use crate::objects::native::{NativeBody, NativeMethod};
+use crate::objects::numeric::{self, Operator};
+
+type Methods = &'static [(&'static str, NativeBody)];
+
+// installs the class of one built-in type, with its methods, as a global of the same name
+#[derive(Clone, Copy)]
+struct ClassSuite {
+    name: &'static str,
+    class: &'static str,
+    dependencies: &'static [&'static str],
+    methods: Methods,
+}
+
+impl BuiltinSuite for ClassSuite {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn dependencies(&self) -> &[&str] {
+        self.dependencies
+    }
+
+    fn install(&self, runtime: &mut Runtime) -> Result<()> {
+        runtime.push_new(Object::new(ClassObject::new(self.class)))?;
+        set_methods(runtime, self.methods)?;
+        runtime.set_global(self.class, 1)?;
+        runtime.pop()
+    }
+}
+
+// adds formatting to the string class, with the receiver as template
+struct FmtSuite;
+
+impl BuiltinSuite for FmtSuite {
+    fn name(&self) -> &str {
+        "fmt"
+    }
+
+    fn dependencies(&self) -> &[&str] {
+        &["string"]
+    }
+
+    fn install(&self, runtime: &mut Runtime) -> Result<()> {
+        runtime.push_global("String")?;
+        set_methods(runtime, &[("format", format), ("print", print)])?;
+        runtime.pop()
+    }
+}
+
+// the class is at index 1, and stays there
+fn set_methods(runtime: &mut Runtime, methods: Methods) -> Result<()> {
+    for (name, body) in methods {
+        let method = runtime.push_new(Object::new(NativeMethod(*body)))?;
+        runtime.with_object_mut(2, |class: &mut ClassObject| {
+            class.set_method(name, method);
+            Ok(())
+        })?;
+        runtime.pop()?;
+    }
+    Ok(())
+}
+
+fn int_to_string(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    runtime.int_to_string(1)?;
+    Ok(1)
+}
+
+fn int_to_float(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    runtime.int_to_float(1)?;
+    Ok(1)
+}
+
+// for ints and floats alike, with the receiver as the left operand and the argument at
+// index 1 as the right one
+fn number_binary(runtime: &mut Runtime, operator: Operator) -> Result<usize> {
+    let right = runtime.get_number(1)?;
+    runtime.push_context();
+    let left = runtime.get_number(1)?;
+    let result = numeric::arithmetic(operator, left, right, runtime.numeric_mode())?;
+    runtime.push_number(result)?;
+    Ok(1)
+}
+
+fn number_add(runtime: &mut Runtime) -> Result<usize> {
+    number_binary(runtime, Operator::Add)
+}
+
+fn number_sub(runtime: &mut Runtime) -> Result<usize> {
+    number_binary(runtime, Operator::Sub)
+}
+
+fn number_mul(runtime: &mut Runtime) -> Result<usize> {
+    number_binary(runtime, Operator::Mul)
+}
+
+fn number_div(runtime: &mut Runtime) -> Result<usize> {
+    number_binary(runtime, Operator::Div)
+}
+
+fn number_mod(runtime: &mut Runtime) -> Result<usize> {
+    number_binary(runtime, Operator::Rem)
+}
+
+fn number_neg(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    let number = runtime.get_number(1)?;
+    let negated = numeric::negate(number, runtime.numeric_mode())?;
+    runtime.push_number(negated)?;
+    Ok(1)
+}
+
+// there is no bool object, so comparisons give int 1 for true and 0 for false; anything
+// compared with NaN is false
+fn number_compare(runtime: &mut Runtime, test: fn(Option<Ordering>) -> bool) -> Result<usize> {
+    let right = runtime.get_number(1)?;
+    runtime.push_context();
+    let left = runtime.get_number(1)?;
+    runtime.push_int(test(numeric::compare(left, right)) as i64)?;
+    Ok(1)
+}
+
+fn number_lt(runtime: &mut Runtime) -> Result<usize> {
+    number_compare(runtime, |ordering| ordering == Some(Ordering::Less))
+}
+
+fn number_le(runtime: &mut Runtime) -> Result<usize> {
+    number_compare(runtime, |ordering| {
+        matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal))
+    })
+}
+
+fn number_gt(runtime: &mut Runtime) -> Result<usize> {
+    number_compare(runtime, |ordering| ordering == Some(Ordering::Greater))
+}
+
+fn number_ge(runtime: &mut Runtime) -> Result<usize> {
+    number_compare(runtime, |ordering| {
+        matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal))
+    })
+}
+
+fn number_eq(runtime: &mut Runtime) -> Result<usize> {
+    number_compare(runtime, |ordering| ordering == Some(Ordering::Equal))
+}
+
+fn float_to_string(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    runtime.float_to_string(1, None)?;
+    Ok(1)
+}
+
+fn float_to_int(runtime: &mut Runtime, mode: RoundMode) -> Result<usize> {
+    runtime.push_context();
+    runtime.float_to_int(1, mode)?;
+    Ok(1)
+}
+
+fn float_trunc(runtime: &mut Runtime) -> Result<usize> {
+    float_to_int(runtime, RoundMode::Trunc)
+}
+
+fn float_round(runtime: &mut Runtime) -> Result<usize> {
+    float_to_int(runtime, RoundMode::Round)
+}
+
+fn float_floor(runtime: &mut Runtime) -> Result<usize> {
+    float_to_int(runtime, RoundMode::Floor)
+}
+
+fn float_ceil(runtime: &mut Runtime) -> Result<usize> {
+    float_to_int(runtime, RoundMode::Ceil)
+}
+
+fn string_parse_int(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    runtime.parse_int(1)?;
+    Ok(1)
+}
+
+fn string_parse_float(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    runtime.parse_float(1)?;
+    Ok(1)
+}
+
+// in chars, not bytes
+fn string_len(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    let len = runtime.get_str(1)?.chars().count();
+    runtime.push_int(len as i64)?;
+    Ok(1)
+}
+
+fn list_len(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    let len = runtime.read_at(1, |list: &ListObject| Ok(list.len()))?;
+    runtime.push_int(len as i64)?;
+    Ok(1)
+}
+
+// nil for a position out of range
+fn list_get(runtime: &mut Runtime) -> Result<usize> {
+    let position = runtime.get_int(1)?;
+    runtime.push_context();
+    let item = runtime.read_at(1, |list: &ListObject| {
+        Ok(usize::try_from(position)
+            .ok()
+            .and_then(|position| list.get(position))
+            .cloned())
+    })?;
+    match item {
+        Some(item) => runtime.push_address(item)?,
+        None => runtime.push_nil()?,
+    }
+    Ok(1)
+}
+
+fn result_unwrap(runtime: &mut Runtime) -> Result<usize> {
+    runtime.push_context();
+    runtime.unwrap_result(1)?;
+    Ok(1)
+}
+
+// the arguments are at index 1 on, the first one filling the first placeholder
+fn arguments(runtime: &mut Runtime) -> Vec<usize> {
+    let count = runtime.stack_len();
+    runtime.push_context();
+    (2..=count + 1).collect()
+}
+
+fn format(runtime: &mut Runtime) -> Result<usize> {
+    let arguments = arguments(runtime);
+    runtime.format(1, &arguments)?;
+    Ok(1)
+}
+
+fn print(runtime: &mut Runtime) -> Result<usize> {
+    let arguments = arguments(runtime);
+    runtime.print(1, &arguments)?;
+    Ok(0)
+}
+
+const CLASS_SUITES: &[ClassSuite] = &[
+    ClassSuite {
+        name: "int",
+        class: "Int",
+        dependencies: &[],
+        methods: &[
+            ("to_string", int_to_string),
+            ("to_float", int_to_float),
+            ("add", number_add),
+            ("sub", number_sub),
+            ("mul", number_mul),
+            ("div", number_div),
+            ("mod", number_mod),
+            ("neg", number_neg),
+            ("lt", number_lt),
+            ("le", number_le),
+            ("gt", number_gt),
+            ("ge", number_ge),
+            ("eq", number_eq),
+        ],
+    },
+    ClassSuite {
+        name: "float",
+        class: "Float",
+        dependencies: &["int"],
+        methods: &[
+            ("to_string", float_to_string),
+            ("trunc", float_trunc),
+            ("round", float_round),
+            ("floor", float_floor),
+            ("ceil", float_ceil),
+            ("add", number_add),
+            ("sub", number_sub),
+            ("mul", number_mul),
+            ("div", number_div),
+            ("mod", number_mod),
+            ("neg", number_neg),
+            ("lt", number_lt),
+            ("le", number_le),
+            ("gt", number_gt),
+            ("ge", number_ge),
+            ("eq", number_eq),
+        ],
+    },
+    // strings parse into ints and floats
+    ClassSuite {
+        name: "string",
+        class: "String",
+        dependencies: &["int", "float"],
+        methods: &[
+            ("parse_int", string_parse_int),
+            ("parse_float", string_parse_float),
+            ("len", string_len),
+        ],
+    },
+    ClassSuite {
+        name: "list",
+        class: "List",
+        dependencies: &["int"],
+        methods: &[("len", list_len), ("get", list_get)],
+    },
+    ClassSuite {
+        name: "result",
+        class: "Result",
+        dependencies: &[],
+        methods: &[("unwrap", result_unwrap)],
+    },
+];
+
+pub fn builtin_suites() -> Suites {
+    let mut suites = Suites::new();
+    for suite in CLASS_SUITES {
+        suites.insert(suite.name.to_owned(), Arc::new(*suite));
+    }
+    suites.insert("fmt".to_owned(), Arc::new(FmtSuite));
+    suites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{self, Write};
+
+    use crate::core::error::Error;
+    use crate::core::runtime::tests::memory;
+    use crate::core::runtime::RuntimeBuilder;
+    use crate::objects::int::IntObject;
+    use crate::objects::numeric::{Number, NumericMode};
+
+    use parking_lot::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Sink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Alias;
+
+    impl BuiltinSuite for Alias {
+        fn name(&self) -> &str {
+            "alias"
+        }
+
+        fn dependencies(&self) -> &[&str] {
+            &["fmt"]
+        }
+
+        fn install(&self, runtime: &mut Runtime) -> Result<()> {
+            runtime.push_global("String")?;
+            runtime.set_global("Str", 1)?;
+            runtime.pop()
+        }
+    }
+
+    struct Loop(&'static str, &'static [&'static str]);
+
+    impl BuiltinSuite for Loop {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn dependencies(&self) -> &[&str] {
+            self.1
+        }
+
+        fn install(&self, _runtime: &mut Runtime) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn installs_after_dependencies() {
+        let (collector, context) = memory(1 << 12);
+        let mut runtime = RuntimeBuilder::new(collector.to_owned(), context.to_owned())
+            .suite(Arc::new(Alias))
+            .install(&["alias"])
+            .boot()
+            .unwrap();
+        assert_eq!(
+            runtime.installed_suites(),
+            &["int", "float", "string", "fmt", "alias"]
+        );
+        runtime.install(&["string", "list"]).unwrap();
+        assert_eq!(runtime.installed_suites().len(), 6);
+        runtime.push_global("Str").unwrap();
+        runtime.push_global("List").unwrap();
+        assert!(runtime.push_global("Dict").is_err());
+        assert!(matches!(
+            runtime.install(&["dict"]),
+            Err(Error::UnknownSuite(ref name)) if name == "dict"
+        ));
+        let err = RuntimeBuilder::new(collector, context)
+            .suite(Arc::new(Loop("a", &["b"])))
+            .suite(Arc::new(Loop("b", &["a"])))
+            .install(&["a"])
+            .boot()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "suites depend on each other: a -> b -> a");
+    }
+
+    #[test]
+    fn invokes_builtin_methods() {
+        let sink = Sink::default();
+        let (collector, context) = memory(1 << 12);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .install(&["fmt", "list", "result"])
+            .stdout(Box::new(sink.to_owned()))
+            .boot()
+            .unwrap();
+
+        runtime.push_int(42).unwrap();
+        runtime.push_global("Int").unwrap();
+        assert_eq!(runtime.invoke(2, 1, "to_string", &[]).unwrap(), 1);
+        assert_eq!(runtime.get_str(1).unwrap(), "42");
+
+        runtime.push_float(2.5).unwrap();
+        runtime.push_global("Float").unwrap();
+        runtime.invoke(2, 1, "round", &[]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 3);
+
+        runtime.push_str("12").unwrap();
+        runtime.push_global("String").unwrap();
+        runtime.invoke(2, 1, "parse_int", &[]).unwrap();
+        runtime.push_global("Result").unwrap();
+        runtime.invoke(2, 1, "unwrap", &[]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 12);
+
+        runtime.push_int(7).unwrap();
+        runtime.push_str("{} + {}").unwrap();
+        runtime.push_global("String").unwrap();
+        runtime.invoke(2, 1, "format", &[3, 3]).unwrap();
+        assert_eq!(runtime.get_str(1).unwrap(), "7 + 7");
+        runtime.push_global("String").unwrap();
+        assert_eq!(runtime.invoke(2, 1, "print", &[]).unwrap(), 0);
+        assert_eq!(&*sink.0.lock(), b"7 + 7");
+        assert!(matches!(
+            runtime.invoke(2, 1, "nope", &[]),
+            Err(Error::NoSuchMethod { .. })
+        ));
+    }
+
+    #[test]
+    fn gets_list_items() {
+        let (collector, context) = memory(1 << 12);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .install(&["list"])
+            .boot()
+            .unwrap();
+        let item = runtime.push_new(Object::with_vtable(IntObject(5))).unwrap();
+        runtime
+            .push_new(Object::new(ListObject::new(vec![item])))
+            .unwrap();
+        runtime.push_global("List").unwrap();
+        runtime.invoke(2, 1, "len", &[]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        runtime.push_int(0).unwrap();
+        runtime.push_global("List").unwrap();
+        // the list is below the zero, the len and the first class
+        runtime.invoke(5, 1, "get", &[2]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 5);
+        runtime.push_int(-1).unwrap();
+        runtime.push_global("List").unwrap();
+        runtime.invoke(8, 1, "get", &[2]).unwrap();
+        assert!(runtime.is_nil(1).unwrap());
+        // the receiver stays where it was
+        assert_eq!(runtime.stack_len(), 10);
+    }
+
+    fn boot_int() -> Runtime {
+        let (collector, context) = memory(1 << 12);
+        RuntimeBuilder::new(collector, context)
+            .install(&["int"])
+            .boot()
+            .unwrap()
+    }
+
+    // leaves the stack as it was
+    fn int_op(runtime: &mut Runtime, left: i64, name: &str, right: Option<i64>) -> Result<i64> {
+        let stack_len = runtime.stack_len();
+        let mut arguments = Vec::new();
+        if let Some(right) = right {
+            runtime.push_int(right)?;
+            arguments.push(3);
+        }
+        runtime.push_int(left)?;
+        runtime.push_global("Int")?;
+        let result = runtime
+            .invoke(2, 1, name, &arguments)
+            .map_err(Error::without_backtrace)
+            .and_then(|_| runtime.get_int(1));
+        while runtime.stack_len() > stack_len {
+            runtime.pop()?;
+        }
+        result
+    }
+
+    #[test]
+    fn int_arithmetic() {
+        let mut runtime = boot_int();
+        let cases = [
+            (7, "add", 6, 13),
+            (7, "sub", 9, -2),
+            (7, "mul", 6, 42),
+            (7, "div", 2, 3),
+            (-7, "div", 2, -3),
+            (7, "mod", 3, 1),
+            (-7, "mod", 3, -1),
+            (1, "lt", 2, 1),
+            (2, "lt", 2, 0),
+            (2, "le", 2, 1),
+            (3, "gt", 2, 1),
+            (2, "ge", 3, 0),
+            (2, "eq", 2, 1),
+            (2, "eq", 3, 0),
+        ];
+        for &(left, name, right, expected) in &cases {
+            assert_eq!(
+                int_op(&mut runtime, left, name, Some(right)).unwrap(),
+                expected,
+                "{} {} {}",
+                left,
+                name,
+                right
+            );
+        }
+        assert_eq!(int_op(&mut runtime, 5, "neg", None).unwrap(), -5);
+        assert_eq!(runtime.stack_len(), 0);
+    }
+
+    #[test]
+    fn int_arithmetic_errors() {
+        let mut runtime = boot_int();
+        for &(left, name, right) in &[
+            (i64::MAX, "add", 1),
+            (i64::MIN, "sub", 1),
+            (i64::MAX, "mul", 2),
+            (i64::MIN, "div", -1),
+            (i64::MIN, "mod", -1),
+        ] {
+            assert!(matches!(
+                int_op(&mut runtime, left, name, Some(right)),
+                Err(Error::IntegerOverflow)
+            ));
+        }
+        assert!(matches!(
+            int_op(&mut runtime, i64::MIN, "neg", None),
+            Err(Error::IntegerOverflow)
+        ));
+        for name in &["div", "mod"] {
+            assert!(matches!(
+                int_op(&mut runtime, 1, name, Some(0)),
+                Err(Error::DivisionByZero)
+            ));
+        }
+        assert_eq!(runtime.stack_len(), 0);
+    }
+
+    #[test]
+    fn chains_int_methods() {
+        let mut runtime = boot_int();
+        // ((7 * 6) - 2) / 4
+        let product = int_op(&mut runtime, 7, "mul", Some(6)).unwrap();
+        let difference = int_op(&mut runtime, product, "sub", Some(2)).unwrap();
+        assert_eq!(
+            int_op(&mut runtime, difference, "div", Some(4)).unwrap(),
+            10
+        );
+    }
+
+    #[test]
+    fn numeric_mode_decides_overflow() {
+        for &(mode, expected) in &[
+            (NumericMode::Strict, None),
+            (
+                NumericMode::Promote,
+                Some(Number::Float(9_223_372_036_854_775_808.0)),
+            ),
+        ] {
+            let (collector, context) = memory(1 << 12);
+            let mut runtime = RuntimeBuilder::new(collector, context)
+                .install(&["float"])
+                .numeric_mode(mode)
+                .boot()
+                .unwrap();
+            runtime.push_int(1).unwrap();
+            runtime.push_int(i64::MAX).unwrap();
+            runtime.push_global("Int").unwrap();
+            let sum = runtime
+                .invoke(2, 1, "add", &[3])
+                .map_err(Error::without_backtrace)
+                .and_then(|_| runtime.get_number(1));
+            match expected {
+                Some(expected) => assert_eq!(sum.unwrap(), expected),
+                None => assert!(matches!(sum, Err(Error::IntegerOverflow))),
+            }
+        }
+    }
+
+    #[test]
+    fn mixes_ints_and_floats() {
+        let (collector, context) = memory(1 << 12);
+        let mut runtime = RuntimeBuilder::new(collector, context)
+            .install(&["float"])
+            .boot()
+            .unwrap();
+        runtime.push_float(0.5).unwrap();
+        runtime.push_int(1).unwrap();
+        runtime.push_global("Int").unwrap();
+        runtime.invoke(2, 1, "add", &[3]).unwrap();
+        assert_eq!(runtime.get_float(1).unwrap(), 1.5);
+        runtime.push_int(1).unwrap();
+        runtime.push_float(1.000_000_000_000_000_2).unwrap();
+        runtime.push_global("Float").unwrap();
+        runtime.invoke(2, 1, "gt", &[3]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 1);
+        runtime.push_global("Float").unwrap();
+        runtime.invoke(4, 1, "eq", &[5]).unwrap();
+        assert_eq!(runtime.get_int(1).unwrap(), 0);
+        runtime.push_str("1").unwrap();
+        runtime.push_global("Int").unwrap();
+        assert!(matches!(
+            runtime
+                .invoke(7, 1, "add", &[2])
+                .map_err(Error::without_backtrace),
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+}
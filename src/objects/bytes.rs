@@ -0,0 +1,130 @@
+//
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::str;
+use std::sync::Arc;
+
+use crate::core::error::{Error, Result};
+
+use hulunbuir::{Address, Keep};
+
+// a window into a shared payload, so slicing and cloning never copy the bytes
+#[derive(Clone)]
+pub struct BytesObject {
+    data: Arc<Vec<u8>>,
+    start: usize,
+    len: usize,
+}
+
+impl Keep for BytesObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+// by content, wherever the window lies in its payload
+impl Hash for BytesObject {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
+}
+
+impl PartialEq for BytesObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl PartialOrd for BytesObject {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.as_slice().partial_cmp(other.as_slice())
+    }
+}
+
+crate::capability!(BytesObject: clone, hash, cmp);
+
+impl BytesObject {
+    pub fn new(data: Vec<u8>) -> Self {
+        let len = data.len();
+        Self {
+            data: Arc::new(data),
+            start: 0,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[self.start..self.start + self.len]
+    }
+
+    // out-of-range bounds are errors rather than being clamped
+    pub fn slice(&self, start: usize, end: usize) -> Result<Self> {
+        if start > end || end > self.len {
+            return Err(Error::IndexOutOfRange);
+        }
+        Ok(Self {
+            data: self.data.clone(),
+            start: self.start + start,
+            len: end - start,
+        })
+    }
+
+    pub fn concat(&self, other: &Self) -> Self {
+        let mut data = Vec::with_capacity(self.len + other.len);
+        data.extend_from_slice(self.as_slice());
+        data.extend_from_slice(other.as_slice());
+        Self::new(data)
+    }
+
+    pub fn get(&self, index: usize) -> Result<u8> {
+        self.as_slice()
+            .get(index)
+            .cloned()
+            .ok_or(Error::IndexOutOfRange)
+    }
+
+    pub fn to_string_utf8(&self) -> Result<String> {
+        str::from_utf8(self.as_slice())
+            .map(ToOwned::to_owned)
+            .map_err(|_| Error::InvalidUtf8)
+    }
+
+    pub fn shares_payload(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn slices_share_payload() {
+        let bytes = BytesObject::new(b"hello world".to_vec());
+        let world = bytes.slice(6, 11).unwrap();
+        assert_eq!(world.to_string_utf8().unwrap(), "world");
+        let or = world.slice(1, 3).unwrap();
+        assert_eq!(or.as_slice(), b"or");
+        assert!(or.shares_payload(&bytes));
+        assert!(bytes.slice(3, 12).is_err());
+        assert!(bytes.slice(4, 3).is_err());
+        assert!(bytes.slice(11, 11).unwrap().is_empty());
+        assert_eq!(bytes.get(0).unwrap(), b'h');
+        assert!(or.get(2).is_err());
+        let joined = world.concat(&or);
+        assert_eq!(joined.as_slice(), b"worldor");
+        assert!(!joined.shares_payload(&bytes));
+        assert!(BytesObject::new(vec![0xff]).to_string_utf8().is_err());
+        let handle = thread::spawn(move || world);
+        assert!(handle.join().unwrap().shares_payload(&bytes));
+    }
+}
@@ -0,0 +1,79 @@
+//
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::core::error::{Error, Result};
+
+use hulunbuir::{slot::Parker, Address, Keep};
+use parking_lot::Mutex;
+
+// clones share the same state, so one can be kept by the canceller while the others
+// are handed to runtimes, or pushed as objects
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    waiting: Mutex<Waiting>,
+}
+
+// wakes threads parked on busy objects, keyed so that they can unregister
+#[derive(Default)]
+struct Waiting {
+    next_id: usize,
+    unparkers: HashMap<usize, Box<dyn Fn() + Send>>,
+}
+
+impl Keep for CancellationToken {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for (_, unpark) in self.inner.waiting.lock().unparkers.drain() {
+            unpark();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    // parks until the parker is unparked or the token is cancelled
+    pub fn park(&self, parker: Parker) -> Result<()> {
+        let unparker = parker.unparker().to_owned();
+        let id = {
+            let mut waiting = self.inner.waiting.lock();
+            let id = waiting.next_id;
+            waiting.next_id += 1;
+            waiting
+                .unparkers
+                .insert(id, Box::new(move || unparker.unpark()));
+            id
+        };
+        // cancelling before the insertion above would not have unparked us
+        if !self.is_cancelled() {
+            parker.park();
+        }
+        self.inner.waiting.lock().unparkers.remove(&id);
+        self.check()
+    }
+}
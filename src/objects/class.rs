@@ -2,8 +2,142 @@
 
 use std::collections::HashMap;
 
-use crate::core::memory::Address;
+use crate::core::error::{Error, Result};
 
-pub struct Class {
-    props: HashMap<String, Address>,
-}
\ No newline at end of file
+use hulunbuir::{Address, Keep};
+
+pub struct ClassObject {
+    name: String,
+    methods: HashMap<String, Address>,
+    // the class asked for methods this one has not
+    parent: Option<Address>,
+}
+
+impl Keep for ClassObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+        for method in self.methods.values() {
+            f(&[method.to_owned()]);
+        }
+        if let Some(parent) = &self.parent {
+            f(&[parent.to_owned()]);
+        }
+    }
+}
+
+impl ClassObject {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            methods: HashMap::new(),
+            parent: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_method(&mut self, name: &str, method: Address) {
+        self.methods.insert(name.to_owned(), method);
+    }
+
+    pub fn set_parent(&mut self, parent: Address) {
+        self.parent = Some(parent);
+    }
+
+    pub fn parent(&self) -> Option<&Address> {
+        self.parent.as_ref()
+    }
+
+    // only the methods of this class itself, see `Runtime::get_method` for the parents
+    pub fn get_method(&self, name: &str) -> Result<&Address> {
+        self.methods.get(name).ok_or_else(|| Error::NoSuchMethod {
+            class: self.name.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::core::object::Object;
+    use crate::core::runtime::tests::{boot_with, Unit};
+    use crate::core::runtime::{Collector, Runtime, Source};
+
+    use hulunbuir::slot::Slot;
+
+    // pushes a class with a method for each name, returning their addresses; the methods
+    // are allocated straight from the collector, which doesn't collect them meanwhile
+    fn push_class(
+        runtime: &mut Runtime,
+        collector: &Collector,
+        class: &str,
+        names: &[&str],
+    ) -> Vec<Address> {
+        let mut class = ClassObject::new(class);
+        let methods = names
+            .iter()
+            .map(|name| {
+                let method = collector
+                    .lock()
+                    .allocate(Slot::new(Object::new(Unit)))
+                    .unwrap();
+                class.set_method(name, method.to_owned());
+                method
+            })
+            .collect();
+        runtime.push_new(Object::new(class)).unwrap();
+        methods
+    }
+
+    fn set_parent(runtime: &mut Runtime, class: usize, parent: usize) {
+        let parent = runtime.load(Source::Local(parent)).unwrap();
+        runtime
+            .with_object_mut(class, |class: &mut ClassObject| {
+                class.set_parent(parent);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn delegates_to_parents() {
+        let (mut runtime, collector) = boot_with();
+        push_class(&mut runtime, &collector, "Leaf", &[]);
+        let middle = push_class(&mut runtime, &collector, "Middle", &["set"]);
+        let base = push_class(&mut runtime, &collector, "Base", &["get", "set"]);
+        set_parent(&mut runtime, 3, 2);
+        set_parent(&mut runtime, 2, 1);
+        assert_eq!(runtime.get_method(3, "set").unwrap(), middle[0]);
+        assert_eq!(runtime.get_method(3, "get").unwrap(), base[0]);
+        assert_eq!(runtime.get_method(2, "set").unwrap(), middle[0]);
+        // the parents are kept by their children only
+        runtime.pop().unwrap();
+        runtime.pop().unwrap();
+        collector.lock().collect().unwrap();
+        assert_eq!(runtime.get_method(1, "get").unwrap(), base[0]);
+        assert_eq!(runtime.get_method(1, "set").unwrap(), middle[0]);
+    }
+
+    #[test]
+    fn misses_through_the_chain() {
+        let (mut runtime, collector) = boot_with();
+        push_class(&mut runtime, &collector, "Base", &["get"]);
+        push_class(&mut runtime, &collector, "Leaf", &[]);
+        set_parent(&mut runtime, 1, 2);
+        let err = runtime.get_method(1, "set").unwrap_err();
+        assert_eq!(err.to_string(), "no method set in class Leaf");
+        // a cycle is a miss as well, instead of looking up forever
+        set_parent(&mut runtime, 2, 1);
+        assert_eq!(
+            runtime.get_method(2, "get").unwrap(),
+            runtime.get_method(1, "get").unwrap()
+        );
+        assert!(matches!(
+            runtime.get_method(1, "set"),
+            Err(Error::NoSuchMethod { .. })
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+//
+
+use std::collections::HashMap;
+
+use crate::core::error::Result;
+
+use hulunbuir::{Address, Keep};
+
+// keyed by objects through their hash and cmp capabilities; only hashes are stored, so
+// comparing keys is up to the caller, who can reach the key objects
+#[derive(Default)]
+pub struct ObjectDict {
+    // entries of colliding keys share a bucket
+    buckets: HashMap<u64, Vec<(Address, Address)>>,
+    len: usize,
+    // bumped by every change, so that iteration can detect it
+    version: u64,
+}
+
+impl Keep for ObjectDict {
+    fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+        for (key, value) in self.buckets.values().flatten() {
+            f(&[key.to_owned(), value.to_owned()]);
+        }
+    }
+}
+
+impl ObjectDict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // in no particular order, but the same one until the dict changes
+    pub fn keys(&self) -> impl Iterator<Item = &Address> {
+        self.buckets.values().flatten().map(|(key, _)| key)
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    // `eq` is asked about the keys in the bucket of `hash` until it answers true
+    pub fn get_with<F>(&self, hash: u64, mut eq: F) -> Result<Option<&Address>>
+    where
+        F: FnMut(&Address) -> Result<bool>,
+    {
+        for (key, value) in self.buckets.get(&hash).into_iter().flatten() {
+            if eq(key)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    // returns the value replaced for an equal key
+    pub fn insert_with<F>(
+        &mut self,
+        hash: u64,
+        key: Address,
+        value: Address,
+        mut eq: F,
+    ) -> Result<Option<Address>>
+    where
+        F: FnMut(&Address) -> Result<bool>,
+    {
+        let bucket = self.buckets.entry(hash).or_default();
+        for entry in bucket.iter_mut() {
+            if eq(&entry.0)? {
+                self.version += 1;
+                return Ok(Some(std::mem::replace(&mut entry.1, value)));
+            }
+        }
+        bucket.push((key, value));
+        self.len += 1;
+        self.version += 1;
+        Ok(None)
+    }
+}
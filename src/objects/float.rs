@@ -0,0 +1,122 @@
+//
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::core::error::{Error, Result};
+use crate::objects::fmt::render_float;
+use crate::objects::int::IntObject;
+
+use hulunbuir::{Address, Keep};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloatObject(pub f64);
+
+impl Keep for FloatObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+impl fmt::Display for FloatObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&render_float(self.0, None))
+    }
+}
+
+// NaN is not ordered, not even against itself, so comparing or sorting it is an error;
+// it still hashes, with every NaN the same, and zeros hash alike as they compare equal
+impl Hash for FloatObject {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let value = if self.0 == 0.0 {
+            0.0
+        } else if self.0.is_nan() {
+            f64::NAN
+        } else {
+            self.0
+        };
+        value.to_bits().hash(state)
+    }
+}
+
+impl PartialOrd for FloatObject {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+}
+
+crate::capability!(FloatObject: debug, display, clone, hash, cmp);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundMode {
+    Trunc,
+    // halfway cases away from zero
+    Round,
+    Floor,
+    Ceil,
+}
+
+impl FloatObject {
+    // `Display` renders the shortest exact form, i.e. without a precision
+    pub fn to_string_with(&self, precision: Option<usize>) -> String {
+        render_float(self.0, precision)
+    }
+
+    // NaN, infinities and anything out of `i64` range after rounding are errors instead
+    // of saturating like `as` does
+    pub fn to_int(&self, mode: RoundMode) -> Result<IntObject> {
+        let rounded = match mode {
+            RoundMode::Trunc => self.0.trunc(),
+            RoundMode::Round => self.0.round(),
+            RoundMode::Floor => self.0.floor(),
+            RoundMode::Ceil => self.0.ceil(),
+        };
+        // -2^63 is exact in `f64`, while `i64::MAX` rounds up to 2^63
+        if !rounded.is_finite() || rounded < i64::MIN as f64 || rounded >= -(i64::MIN as f64) {
+            return Err(Error::FloatToInt(self.0));
+        }
+        Ok(IntObject(rounded as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::objects::string::StringObject;
+
+    fn string(value: &str) -> StringObject {
+        StringObject(value.to_owned())
+    }
+
+    #[test]
+    fn parses_and_renders_round_trip() {
+        assert_eq!(string("-42").parse_int().unwrap().0, -42);
+        let min = IntObject(i64::MIN).to_string();
+        assert_eq!(min, "-9223372036854775808");
+        assert_eq!(string(&min).parse_int().unwrap().0, i64::MIN);
+        assert!(string("1,5").parse_float().is_err());
+        assert!(string("NaN").parse_float().is_err());
+        assert!(string("inf").parse_float().is_err());
+        assert_eq!(string("1e300").parse_float().unwrap().0, 1e300);
+        assert_eq!(FloatObject(1e300).to_string(), "1e300");
+        assert_eq!(FloatObject(-2.0).to_string(), "-2.0");
+        assert_eq!(FloatObject(2.5).to_string_with(Some(3)), "2.500");
+        assert_eq!(FloatObject(2.5).to_string_with(None), "2.5");
+        assert_eq!(IntObject(-7).to_float().0, -7.0);
+    }
+
+    #[test]
+    fn rounds_to_int_in_every_mode() {
+        for &(value, trunc, round, floor, ceil) in &[(2.5, 2, 3, 2, 3), (-2.5, -2, -3, -3, -2)] {
+            let float = FloatObject(value);
+            assert_eq!(float.to_int(RoundMode::Trunc).unwrap().0, trunc);
+            assert_eq!(float.to_int(RoundMode::Round).unwrap().0, round);
+            assert_eq!(float.to_int(RoundMode::Floor).unwrap().0, floor);
+            assert_eq!(float.to_int(RoundMode::Ceil).unwrap().0, ceil);
+        }
+        assert!(FloatObject(f64::NAN).to_int(RoundMode::Trunc).is_err());
+        assert!(FloatObject(f64::INFINITY).to_int(RoundMode::Floor).is_err());
+        assert!(FloatObject(9.3e18).to_int(RoundMode::Trunc).is_err());
+        let min = FloatObject(i64::MIN as f64).to_int(RoundMode::Trunc);
+        assert_eq!(min.unwrap().0, i64::MIN);
+    }
+}
@@ -0,0 +1,55 @@
+//
+
+use crate::core::error::{Error, Result};
+use crate::core::object::Object;
+
+// prefers the display capability, then debug, then the type name
+pub fn render(object: &Object) -> String {
+    if let Some(display) = object.display_string() {
+        return display;
+    }
+    if object.vtable().debug.is_some() {
+        return format!("{:?}", object);
+    }
+    format!("<{}>", object.type_name())
+}
+
+// the one rendering of floats, which always shows a decimal point unless written with
+// an exponent, and never depends on the locale
+pub fn render_float(value: f64, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => format!("{:.*}", precision, value),
+        None => format!("{:?}", value),
+    }
+}
+
+// fills `{}` placeholders in order, with `{{` and `}}` for literal braces
+pub fn format(template: &str, arguments: &[String]) -> Result<String> {
+    let mut output = String::new();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                output.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(argument) = arguments.get(placeholders) {
+                    output.push_str(argument);
+                }
+                placeholders += 1;
+            }
+            ('{', _) | ('}', _) => return Err(Error::InvalidFormat),
+            _ => output.push(c),
+        }
+    }
+    if placeholders != arguments.len() {
+        return Err(Error::FormatArguments {
+            placeholders,
+            arguments: arguments.len(),
+        });
+    }
+    Ok(output)
+}
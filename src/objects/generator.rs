@@ -0,0 +1,51 @@
+//
+
+use crate::core::error::{Error, Result};
+use crate::core::runtime::Runtime;
+
+use hulunbuir::{Address, Keep};
+
+pub(crate) type Step = Box<dyn FnMut(&mut Runtime) -> Result<()>>;
+
+// created by `Runtime::call_generator`, and driven by `Runtime::resume_generator`
+pub struct GeneratorObject {
+    // `None` once the generator is finished
+    frame: Option<Address>,
+    // taken out while the generator is running
+    step: Option<Step>,
+}
+
+impl Keep for GeneratorObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+        if let Some(frame) = &self.frame {
+            f(&[frame.to_owned()]);
+        }
+    }
+}
+
+impl GeneratorObject {
+    pub(crate) fn new(frame: Address, step: Step) -> Self {
+        Self {
+            frame: Some(frame),
+            step: Some(step),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame.is_none()
+    }
+
+    pub(crate) fn start(&mut self) -> Result<(Address, Step)> {
+        let frame = self.frame.to_owned().ok_or(Error::GeneratorFinished)?;
+        let step = self.step.take().ok_or(Error::BusyObject)?;
+        Ok((frame, step))
+    }
+
+    pub(crate) fn pause(&mut self, step: Step) {
+        self.step = Some(step);
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.frame = None;
+    }
+}
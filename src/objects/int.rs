@@ -0,0 +1,80 @@
+//
+
+use std::fmt;
+
+use crate::core::error::{Error, Result};
+use crate::objects::float::FloatObject;
+
+use hulunbuir::{Address, Keep};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IntObject(pub i64);
+
+impl Keep for IntObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+impl fmt::Display for IntObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+crate::capability!(IntObject: debug, display, clone, hash, cmp);
+
+impl IntObject {
+    // exact up to 2^53, rounded to the nearest float beyond
+    pub fn to_float(&self) -> FloatObject {
+        FloatObject(self.0 as f64)
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self> {
+        self.0
+            .checked_add(other.0)
+            .map(IntObject)
+            .ok_or(Error::IntegerOverflow)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self> {
+        self.0
+            .checked_sub(other.0)
+            .map(IntObject)
+            .ok_or(Error::IntegerOverflow)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Result<Self> {
+        self.0
+            .checked_mul(other.0)
+            .map(IntObject)
+            .ok_or(Error::IntegerOverflow)
+    }
+
+    // rounds toward zero, like `/` on `i64`
+    pub fn checked_div(self, other: Self) -> Result<Self> {
+        if other.0 == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        self.0
+            .checked_div(other.0)
+            .map(IntObject)
+            .ok_or(Error::IntegerOverflow)
+    }
+
+    // takes the sign of the dividend, like `%` on `i64`
+    pub fn checked_rem(self, other: Self) -> Result<Self> {
+        if other.0 == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        self.0
+            .checked_rem(other.0)
+            .map(IntObject)
+            .ok_or(Error::IntegerOverflow)
+    }
+
+    pub fn checked_neg(self) -> Result<Self> {
+        self.0
+            .checked_neg()
+            .map(IntObject)
+            .ok_or(Error::IntegerOverflow)
+    }
+}
@@ -0,0 +1,52 @@
+//
+
+use hulunbuir::{Address, Keep};
+
+pub struct ListObject {
+    items: Vec<Address>,
+    // bumped by every change, so that iteration can detect it
+    version: u64,
+}
+
+impl Keep for ListObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+        f(&self.items)
+    }
+}
+
+impl ListObject {
+    pub fn new(items: Vec<Address>) -> Self {
+        Self { items, version: 0 }
+    }
+
+    pub fn items(&self) -> &[Address] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Address> {
+        self.items.get(index)
+    }
+
+    pub fn push(&mut self, item: Address) {
+        self.items.push(item);
+        self.version += 1;
+    }
+
+    // replaces all items at once, e.g. with the same ones sorted
+    pub fn set_items(&mut self, items: Vec<Address>) {
+        self.items = items;
+        self.version += 1;
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
@@ -1,4 +1,20 @@
 //
 
-pub mod thread;
-// pub mod class;
+pub mod atomic;
+pub mod builtin;
+pub mod bytes;
+pub mod cancel;
+pub mod class;
+pub mod dict;
+pub mod float;
+pub mod fmt;
+pub mod generator;
+pub mod int;
+pub mod list;
+pub mod native;
+pub mod nil;
+pub mod numeric;
+pub mod result;
+pub mod string;
+pub mod time;
+// pub mod thread;
@@ -0,0 +1,16 @@
+//
+
+use crate::core::error::Result;
+use crate::core::runtime::Runtime;
+
+use hulunbuir::{Address, Keep};
+
+// runs in the callee's frame, with the receiver as context and the arguments from index
+// 1 on, and returns how many values on top of its stack go back to the caller
+pub type NativeBody = fn(&mut Runtime) -> Result<usize>;
+
+pub struct NativeMethod(pub NativeBody);
+
+impl Keep for NativeMethod {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
@@ -0,0 +1,27 @@
+//
+
+use std::fmt;
+
+use hulunbuir::{Address, Keep};
+
+// there is one nil per runtime, created while booting, so nil can be told by identity
+#[derive(Debug)]
+pub struct NilObject(());
+
+impl NilObject {
+    pub(crate) fn new() -> Self {
+        NilObject(())
+    }
+}
+
+impl Keep for NilObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+impl fmt::Display for NilObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nil")
+    }
+}
+
+crate::capability!(NilObject: debug, display);
@@ -0,0 +1,211 @@
+//
+
+use std::cmp::Ordering;
+
+use crate::core::error::{Error, Result};
+use crate::objects::int::IntObject;
+
+// what int arithmetic does on overflow; arithmetic involving a float is float arithmetic
+// in either mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumericMode {
+    // overflow is `IntegerOverflow`
+    #[default]
+    Strict,
+    // overflow redoes the operation on floats, losing precision beyond 2^53
+    Promote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    // ints round toward zero
+    Div,
+    // takes the sign of the dividend, for ints and floats alike
+    Rem,
+}
+
+impl Number {
+    fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(value) => value as f64,
+            Number::Float(value) => value,
+        }
+    }
+}
+
+// dividing an int by int zero is `DivisionByZero` in either mode, while floats follow
+// IEEE 754 and give an infinity or NaN
+pub fn arithmetic(
+    operator: Operator,
+    left: Number,
+    right: Number,
+    mode: NumericMode,
+) -> Result<Number> {
+    if let (Number::Int(left), Number::Int(right)) = (left, right) {
+        let (left, right) = (IntObject(left), IntObject(right));
+        let checked = match operator {
+            Operator::Add => left.checked_add(right),
+            Operator::Sub => left.checked_sub(right),
+            Operator::Mul => left.checked_mul(right),
+            Operator::Div => left.checked_div(right),
+            Operator::Rem => left.checked_rem(right),
+        };
+        match (checked, mode) {
+            (Ok(result), _) => return Ok(Number::Int(result.0)),
+            (Err(Error::IntegerOverflow), NumericMode::Promote) => {}
+            (Err(err), _) => return Err(err),
+        }
+    }
+    let (left, right) = (left.to_f64(), right.to_f64());
+    Ok(Number::Float(match operator {
+        Operator::Add => left + right,
+        Operator::Sub => left - right,
+        Operator::Mul => left * right,
+        Operator::Div => left / right,
+        Operator::Rem => left % right,
+    }))
+}
+
+pub fn negate(number: Number, mode: NumericMode) -> Result<Number> {
+    match (number, mode) {
+        (Number::Int(value), _) if value != i64::MIN => Ok(Number::Int(-value)),
+        (Number::Int(_), NumericMode::Strict) => Err(Error::IntegerOverflow),
+        (number, _) => Ok(Number::Float(-number.to_f64())),
+    }
+}
+
+// exact across types, so an int equals a float only if the float has exactly its value;
+// `None` only when NaN is involved
+pub fn compare(left: Number, right: Number) -> Option<Ordering> {
+    match (left, right) {
+        (Number::Int(left), Number::Int(right)) => Some(left.cmp(&right)),
+        (Number::Float(left), Number::Float(right)) => left.partial_cmp(&right),
+        (Number::Int(left), Number::Float(right)) => compare_exact(left, right),
+        (Number::Float(left), Number::Int(right)) => {
+            compare_exact(right, left).map(Ordering::reverse)
+        }
+    }
+}
+
+fn compare_exact(int: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    // -2^63 is exact in `f64`, 2^63 is the first float above `i64::MAX`
+    if float >= -(i64::MIN as f64) {
+        return Some(Ordering::Less);
+    }
+    if float < i64::MIN as f64 {
+        return Some(Ordering::Greater);
+    }
+    // the integral part converts exactly, whatever is left decides a tie
+    let integral = float.trunc();
+    Some(
+        int.cmp(&(integral as i64))
+            .then_with(|| 0.0.partial_cmp(&(float - integral)).unwrap()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Number::{Float, Int};
+
+    #[test]
+    fn overflows_by_mode() {
+        let sum = arithmetic(Operator::Add, Int(i64::MAX), Int(1), NumericMode::Strict);
+        assert!(matches!(sum, Err(Error::IntegerOverflow)));
+        let sum = arithmetic(Operator::Add, Int(i64::MAX), Int(1), NumericMode::Promote);
+        assert_eq!(sum.unwrap(), Float(9_223_372_036_854_775_808.0));
+        let quotient = arithmetic(Operator::Div, Int(i64::MIN), Int(-1), NumericMode::Promote);
+        assert_eq!(quotient.unwrap(), Float(9_223_372_036_854_775_808.0));
+        assert!(negate(Int(i64::MIN), NumericMode::Strict).is_err());
+        assert_eq!(
+            negate(Int(i64::MIN), NumericMode::Promote).unwrap(),
+            Float(9_223_372_036_854_775_808.0)
+        );
+        // a zero divisor is not an overflow, so it is not promoted
+        for &mode in &[NumericMode::Strict, NumericMode::Promote] {
+            for &operator in &[Operator::Div, Operator::Rem] {
+                assert!(matches!(
+                    arithmetic(operator, Int(1), Int(0), mode),
+                    Err(Error::DivisionByZero)
+                ));
+            }
+        }
+        let quotient = arithmetic(Operator::Div, Float(1.0), Int(0), NumericMode::Strict);
+        assert_eq!(quotient.unwrap(), Float(f64::INFINITY));
+    }
+
+    #[test]
+    fn promotes_mixed_operands() {
+        let sum = arithmetic(Operator::Add, Int(1), Float(0.5), NumericMode::Strict);
+        assert_eq!(sum.unwrap(), Float(1.5));
+        let product = arithmetic(Operator::Mul, Float(0.5), Int(3), NumericMode::Strict);
+        assert_eq!(product.unwrap(), Float(1.5));
+    }
+
+    #[test]
+    fn divides_negative_operands() {
+        let mode = NumericMode::Strict;
+        for &(left, right, quotient, remainder) in &[
+            (7, 2, 3, 1),
+            (-7, 2, -3, -1),
+            (7, -2, -3, 1),
+            (-7, -2, 3, -1),
+        ] {
+            let divided = arithmetic(Operator::Div, Int(left), Int(right), mode);
+            assert_eq!(divided.unwrap(), Int(quotient));
+            let rem = arithmetic(Operator::Rem, Int(left), Int(right), mode);
+            assert_eq!(rem.unwrap(), Int(remainder));
+            let rem = arithmetic(Operator::Rem, Float(left as f64), Int(right), mode);
+            assert_eq!(rem.unwrap(), Float(remainder as f64));
+        }
+        let divided = arithmetic(Operator::Div, Int(-7), Float(2.0), mode);
+        assert_eq!(divided.unwrap(), Float(-3.5));
+    }
+
+    #[test]
+    fn compares_exactly_across_types() {
+        assert_eq!(compare(Int(1), Float(1.0)), Some(Ordering::Equal));
+        assert_eq!(
+            compare(Int(1), Float(1.000_000_000_000_000_2)),
+            Some(Ordering::Less)
+        );
+        // 2^53 + 1 has no float, and converting it would round to 2^53
+        let above = (1i64 << 53) + 1;
+        assert_eq!(
+            compare(Int(above), Float((1u64 << 53) as f64)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(Float((1u64 << 53) as f64), Int(above)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(Int(i64::MAX), Float(i64::MAX as f64)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(Int(i64::MIN), Float(i64::MIN as f64)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare(Int(i64::MIN), Float(-1e19)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(compare(Int(-1), Float(-1.5)), Some(Ordering::Greater));
+        assert_eq!(compare(Int(-2), Float(-1.5)), Some(Ordering::Less));
+        assert_eq!(compare(Int(0), Float(f64::NAN)), None);
+    }
+}
@@ -0,0 +1,29 @@
+//
+
+use hulunbuir::{Address, Keep};
+
+pub enum ResultObject {
+    Ok(Address),
+    Err(Address),
+}
+
+impl Keep for ResultObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, mut f: F) {
+        f(&[self.payload().to_owned()])
+    }
+}
+
+impl ResultObject {
+    pub fn is_ok(&self) -> bool {
+        match self {
+            ResultObject::Ok(_) => true,
+            ResultObject::Err(_) => false,
+        }
+    }
+
+    pub fn payload(&self) -> &Address {
+        match self {
+            ResultObject::Ok(payload) | ResultObject::Err(payload) => payload,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//
+
+use std::fmt;
+
+use crate::core::error::{Error, Result};
+use crate::objects::float::FloatObject;
+use crate::objects::int::IntObject;
+
+use hulunbuir::{Address, Keep};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StringObject(pub String);
+
+impl Keep for StringObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+impl fmt::Display for StringObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+crate::capability!(StringObject: debug, display, clone, hash, cmp);
+
+impl StringObject {
+    pub fn parse_int(&self) -> Result<IntObject> {
+        self.0
+            .parse()
+            .map(IntObject)
+            .map_err(|_| self.parse_error("int"))
+    }
+
+    // NaN and infinities are rejected even though Rust spells them out
+    pub fn parse_float(&self) -> Result<FloatObject> {
+        match self.0.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(FloatObject(value)),
+            _ => Err(self.parse_error("float")),
+        }
+    }
+
+    fn parse_error(&self, target_type: &'static str) -> Error {
+        Error::ParseError {
+            input: self.0.to_owned(),
+            target_type,
+        }
+    }
+}
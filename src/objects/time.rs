@@ -0,0 +1,48 @@
+//
+
+use std::time::Duration;
+
+use hulunbuir::{Address, Keep};
+
+// times are read from the runtime's scheduler, so they follow a virtual clock as well
+#[derive(Debug, Clone)]
+pub struct DeadlineObject {
+    at: Duration,
+}
+
+impl Keep for DeadlineObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+crate::capability!(DeadlineObject: debug, clone);
+
+impl DeadlineObject {
+    pub fn new(now: Duration, timeout: Duration) -> Self {
+        Self { at: now + timeout }
+    }
+
+    pub fn expired(&self, now: Duration) -> bool {
+        now >= self.at
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StopwatchObject {
+    started: Duration,
+}
+
+impl Keep for StopwatchObject {
+    fn with_keep<F: FnMut(&[Address])>(&self, _f: F) {}
+}
+
+crate::capability!(StopwatchObject: debug, clone);
+
+impl StopwatchObject {
+    pub fn start(now: Duration) -> Self {
+        Self { started: now }
+    }
+
+    pub fn elapsed(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.started)
+    }
+}
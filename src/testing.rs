@@ -0,0 +1,276 @@
+//
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use crate::core::error::Result;
+use crate::core::object::Object;
+use crate::core::runtime::{Collector, Runtime, RuntimeBuilder};
+use crate::core::sched::{RecordingScheduler, ReplayScheduler, Scheduler, YieldEvent};
+use crate::objects::atomic::AtomicIntObject;
+use crate::objects::list::ListObject;
+
+use hulunbuir::{slot::Slot, Collector as RawCollector};
+use parking_lot::Mutex;
+
+const RACE_COLLECTOR_SIZE: usize = 1 << 12;
+
+pub type RaceThread = Box<dyn FnOnce(&mut Runtime) -> Result<Vec<i64>> + Send>;
+
+// every thread boots its own runtime with the shared objects pushed in order, so the last
+// one is at index 1; the values a thread returns are what it observed. Only the
+// operations going through the runtime, e.g. `Runtime::atomic_load`, are recorded
+pub struct RaceCase {
+    shared: Vec<AtomicIntObject>,
+    threads: Vec<RaceThread>,
+    replay: Option<Vec<YieldEvent>>,
+}
+
+impl RaceCase {
+    pub fn new(shared: Vec<AtomicIntObject>) -> Self {
+        Self {
+            shared,
+            threads: Vec::new(),
+            replay: None,
+        }
+    }
+
+    // runs the case in the interleaving of a `RaceFailure` instead of a free one
+    pub fn replay(mut self, events: Vec<YieldEvent>) -> Self {
+        self.replay = Some(events);
+        self
+    }
+
+    pub fn thread<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Runtime) -> Result<Vec<i64>> + Send + 'static,
+    {
+        self.threads.push(Box::new(f));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThreadOutcome {
+    Values(Vec<i64>),
+    Error(String),
+    Panic(String),
+}
+
+// in the order the threads were added to the case
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RaceOutcome {
+    pub threads: Vec<ThreadOutcome>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaceFailure {
+    pub iteration: usize,
+    pub outcome: RaceOutcome,
+    // feed to `RaceCase::replay` to run the same interleaving again
+    pub events: Vec<YieldEvent>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RaceReport {
+    pub iterations: usize,
+    pub outcomes: HashMap<RaceOutcome, usize>,
+    pub first_failure: Option<RaceFailure>,
+}
+
+impl RaceReport {
+    pub fn passed(&self) -> bool {
+        self.first_failure.is_none()
+    }
+}
+
+pub fn run_race<S, C>(iterations: usize, setup: S, check: C) -> RaceReport
+where
+    S: Fn() -> RaceCase,
+    C: Fn(RaceOutcome) -> bool,
+{
+    let mut report = RaceReport {
+        iterations,
+        ..RaceReport::default()
+    };
+    for iteration in 0..iterations {
+        let (outcome, events) = run_case(setup());
+        *report.outcomes.entry(outcome.to_owned()).or_insert(0) += 1;
+        if report.first_failure.is_none() && !check(outcome.to_owned()) {
+            report.first_failure = Some(RaceFailure {
+                iteration,
+                outcome,
+                events,
+            });
+        }
+    }
+    report
+}
+
+// records the replayed run as well, which gives the same events back once it finishes
+struct Replaying {
+    replay: ReplayScheduler,
+    recording: Arc<RecordingScheduler>,
+}
+
+impl Scheduler for Replaying {
+    fn yield_point(&self, tag: &str) {
+        self.atomic_point(tag, &mut || {})
+    }
+
+    fn atomic_point(&self, tag: &str, f: &mut dyn FnMut()) {
+        self.replay
+            .atomic_point(tag, &mut || self.recording.atomic_point(tag, f))
+    }
+}
+
+fn run_case(case: RaceCase) -> (RaceOutcome, Vec<YieldEvent>) {
+    let RaceCase {
+        shared,
+        threads,
+        replay,
+    } = case;
+    let recording = Arc::new(RecordingScheduler::new());
+    let scheduler: Arc<dyn Scheduler + Send + Sync> = match replay {
+        Some(events) => Arc::new(Replaying {
+            replay: ReplayScheduler::new(events),
+            recording: recording.to_owned(),
+        }),
+        None => recording.to_owned(),
+    };
+    let handles: Vec<_> = threads
+        .into_iter()
+        .enumerate()
+        .map(|(index, f)| {
+            let shared = shared.to_owned();
+            let scheduler = scheduler.to_owned();
+            // named after their position, so that recorded events can be replayed
+            thread::Builder::new()
+                .name(format!("race-{}", index))
+                .spawn(move || -> std::result::Result<Vec<i64>, String> {
+                    let mut runtime =
+                        boot_race(shared, scheduler).map_err(|err| err.to_string())?;
+                    f(&mut runtime).map_err(|err| err.to_string())
+                })
+                .unwrap()
+        })
+        .collect();
+    let threads = handles
+        .into_iter()
+        .map(|handle| match handle.join() {
+            Ok(Ok(values)) => ThreadOutcome::Values(values),
+            Ok(Err(err)) => ThreadOutcome::Error(err),
+            Err(payload) => ThreadOutcome::Panic(panic_message(payload)),
+        })
+        .collect();
+    (RaceOutcome { threads }, recording.events())
+}
+
+fn boot_race(
+    shared: Vec<AtomicIntObject>,
+    scheduler: Arc<dyn Scheduler + Send + Sync>,
+) -> Result<Runtime> {
+    // `Collector` is an `Arc` by definition, even though this one stays on its thread
+    #[allow(clippy::arc_with_non_send_sync)]
+    let collector: Collector = Arc::new(Mutex::new(RawCollector::new(RACE_COLLECTOR_SIZE)));
+    let context = collector
+        .lock()
+        .allocate(Slot::new(Object::new(ListObject::new(Vec::new()))))?;
+    let mut runtime = RuntimeBuilder::new(collector, context)
+        .collector_capacity(RACE_COLLECTOR_SIZE)
+        .with_scheduler(scheduler)
+        .boot()?;
+    for object in shared {
+        runtime.push_new(Object::with_vtable(object))?;
+    }
+    Ok(runtime)
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => (*message).to_owned(),
+            Err(_) => "<panic>".to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[i64]) -> ThreadOutcome {
+        ThreadOutcome::Values(values.to_vec())
+    }
+
+    // one thread increments 42 while the other reads it
+    fn increment_and_read() -> RaceCase {
+        RaceCase::new(vec![AtomicIntObject::new(42)])
+            .thread(|runtime| Ok(vec![runtime.atomic_fetch_add(1, 1)? + 1]))
+            .thread(|runtime| Ok(vec![runtime.atomic_load(1)?]))
+    }
+
+    #[test]
+    fn single_thread_is_deterministic() {
+        let report = run_race(
+            5,
+            || {
+                RaceCase::new(vec![AtomicIntObject::new(1)])
+                    .thread(|runtime| Ok(vec![runtime.atomic_fetch_add(1, 1)? + 1]))
+            },
+            |outcome| outcome.threads == vec![values(&[2])],
+        );
+        assert!(report.passed());
+        assert_eq!(report.outcomes.len(), 1);
+    }
+
+    #[test]
+    fn two_threads_see_either_order() {
+        let report = run_race(20, increment_and_read, |outcome| {
+            let expected = [
+                vec![values(&[43]), values(&[42])],
+                vec![values(&[43]), values(&[43])],
+            ];
+            expected.contains(&outcome.threads)
+        });
+        assert!(report.passed(), "{:?}", report);
+    }
+
+    #[test]
+    fn captures_panics() {
+        let report = run_race(
+            2,
+            || RaceCase::new(Vec::new()).thread(|_| panic!("boom")),
+            |outcome| outcome.threads == vec![ThreadOutcome::Panic("boom".to_owned())],
+        );
+        assert!(report.passed());
+        let report = run_race(
+            3,
+            || RaceCase::new(Vec::new()).thread(|_| Ok(vec![1])),
+            |_| false,
+        );
+        assert_eq!(report.first_failure.unwrap().iteration, 0);
+    }
+
+    #[test]
+    fn replays_failing_interleaving() {
+        let report = run_race(1, increment_and_read, |_| false);
+        let failure = report.first_failure.unwrap();
+        assert!(failure
+            .events
+            .iter()
+            .any(|event| event.tag == "atomic_load"));
+        for _ in 0..20 {
+            let events = failure.events.to_owned();
+            let replayed = run_race(
+                1,
+                || increment_and_read().replay(events.to_owned()),
+                |outcome| outcome == failure.outcome,
+            );
+            assert!(replayed.passed(), "{:?}", replayed);
+        }
+    }
+}